@@ -1,7 +1,9 @@
+use std::collections::HashMap;
 use std::fmt::Display;
-use std::fs::{File, create_dir_all};
+use std::fs::{File, create_dir_all, metadata};
 use std::io::Write;
 use std::path::{PathBuf, Path};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::Result;
 
@@ -10,8 +12,11 @@ use util::buildk_output::{BuildkOutput, WithBKOutput};
 use util::{PartialConclusion, paths};
 
 use crate::{dependency_fingerprint, file_fingerprint};
-use crate::data::CacheData;
+use crate::data::{CacheData, Stamp};
 use crate::output::Output;
+use crate::source::Source;
+
+const RACY_THRESHOLD: Duration = Duration::from_secs(2);
 
 #[derive(Clone)]
 pub struct Cache {
@@ -90,11 +95,75 @@ impl Cache {
         self.data.contains_key(key)
     }
 
+    pub fn source(&self, path: &Path) -> Option<&Source> {
+        self.data.source(path)
+    }
+
+    pub fn sources(&self) -> &HashMap<PathBuf, Source> {
+        self.data.sources()
+    }
+
+    pub fn insert_source(&mut self, path: PathBuf, source: Source) {
+        self.data.insert_source(path, source);
+        self.dirty = true;
+    }
+
+    pub fn remove_source(&mut self, path: &Path) {
+        self.data.remove_source(path);
+        self.dirty = true;
+    }
+
+    /// Fingerprint of the kotlinc options the recorded sources were compiled with.
+    pub fn kotlinc_fingerprint(&self) -> u64 {
+        self.data.kotlinc_fingerprint()
+    }
+
+    pub fn set_kotlinc_fingerprint(&mut self, fingerprint: u64) {
+        if self.data.kotlinc_fingerprint() != fingerprint {
+            self.data.set_kotlinc_fingerprint(fingerprint);
+            self.dirty = true;
+        }
+    }
+
+    /// Content fingerprint of a file. Hashing is skipped when the size and modification time
+    /// are the same as last time the file was hashed.
+    pub fn fingerprint(&mut self, path: &Path) -> Result<u64> {
+        let meta = metadata(path)?;
+        let mtime = paths::modification_time(path)?;
+        let (seconds, nanos, size) = (mtime.unix_seconds(), mtime.nanoseconds(), meta.len());
+
+        if let Some(stamp) = self.data.stamp(path) {
+            if stamp.seconds == seconds && stamp.nanos == nanos && stamp.size == size {
+                return Ok(stamp.fingerprint);
+            }
+        }
+
+        let fingerprint = file_fingerprint(path)?;
+
+        // a file written within the timestamp granularity may change again without
+        // a new mtime, it is hashed again next time instead.
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        if now.saturating_sub(Duration::from_secs(seconds.max(0) as u64)) > RACY_THRESHOLD {
+            let stamp = Stamp { seconds, nanos, size, fingerprint };
+            self.data.insert_stamp(path.to_path_buf(), stamp);
+            self.dirty = true;
+        }
+
+        Ok(fingerprint)
+    }
+
+    /// Forget the stamps of files that are not matched by `keep`, e.g. deleted sources.
+    pub fn retain_stamps(&mut self, keep: impl Fn(&Path) -> bool) {
+        if self.data.retain_stamps(keep) {
+            self.dirty = true;
+        }
+    }
+
     pub fn cache_file(
         &mut self,
-        file: &PathBuf,
+        file: &Path,
     ) -> Result<PartialConclusion> {
-        let key = file_fingerprint(file)?;
+        let key = self.fingerprint(file)?;
         match self.data.contains_key(&key) {
             true => Ok(PartialConclusion::CACHED),
             false => {
@@ -125,8 +194,10 @@ impl Cache {
         }
     }
 
+    /// Forget everything, only writing the emptied cache back if it still exists on disk.
     pub fn invalidate(&mut self) {
-        self.dirty = false;
+        self.data = CacheData::default();
+        self.dirty = self.location.exists();
     }
 }
 
@@ -1,14 +1,28 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{collections::HashMap, fmt::Display, path::{Path, PathBuf}};
 
 use serde_derive::{Deserialize, Serialize};
 
 use crate::output::Output;
+use crate::source::Source;
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct CacheData {
     kotlinc_fingerprint: u64,
     outputs: HashMap<u64, Output>,
     successes: HashMap<u64, bool>,
+    #[serde(default)]
+    sources: HashMap<PathBuf, Source>,
+    #[serde(default)]
+    stamps: HashMap<PathBuf, Stamp>,
+}
+
+/// Size and modification time of a file when its content fingerprint was computed.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Stamp {
+    pub seconds: i64,
+    pub nanos: u32,
+    pub size: u64,
+    pub fingerprint: u64,
 }
 
 impl CacheData {
@@ -23,6 +37,45 @@ impl CacheData {
     pub fn insert(&mut self, key: u64, value: Output) {
         self.outputs.insert(key, value);
     }
+
+    pub fn source(&self, path: &Path) -> Option<&Source> {
+        self.sources.get(path)
+    }
+
+    pub fn sources(&self) -> &HashMap<PathBuf, Source> {
+        &self.sources
+    }
+
+    pub fn insert_source(&mut self, path: PathBuf, source: Source) {
+        self.sources.insert(path, source);
+    }
+
+    pub fn remove_source(&mut self, path: &Path) {
+        self.sources.remove(path);
+    }
+
+    pub fn kotlinc_fingerprint(&self) -> u64 {
+        self.kotlinc_fingerprint
+    }
+
+    pub fn set_kotlinc_fingerprint(&mut self, fingerprint: u64) {
+        self.kotlinc_fingerprint = fingerprint
+    }
+
+    pub fn stamp(&self, path: &Path) -> Option<&Stamp> {
+        self.stamps.get(path)
+    }
+
+    pub fn insert_stamp(&mut self, path: PathBuf, stamp: Stamp) {
+        self.stamps.insert(path, stamp);
+    }
+
+    /// Keep the stamps of the paths matching `keep`, returns whether any were dropped.
+    pub fn retain_stamps(&mut self, keep: impl Fn(&Path) -> bool) -> bool {
+        let len = self.stamps.len();
+        self.stamps.retain(|path, _| keep(path));
+        self.stamps.len() != len
+    }
 }
 
 impl Display for CacheData {
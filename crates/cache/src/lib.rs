@@ -1,30 +1,63 @@
+use std::fs::File;
 use std::hash::{Hash, Hasher};
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::Path;
 
 use anyhow::ensure;
 
-use dependency::Package;
-use paths::modification_time;
+use dependency::{resolve_descriptor, Package};
 use util::hasher::StableHasher;
-use util::paths;
 
 pub mod cache;
 mod data;
 pub mod output;
+pub mod source;
 
 
-pub fn file_fingerprint(path: &PathBuf) -> anyhow::Result<u64> {
+/// Fingerprint of the file contents, so it survives checkouts, restores and other machines.
+pub fn file_fingerprint(path: &Path) -> anyhow::Result<u64> {
+    ensure!(path.is_file(), "{} is not a file", path.display());
     let mut hasher = StableHasher::default();
-    ensure!(path.is_file());
-    path.hash(&mut hasher);
-    modification_time(path)?.hash(&mut hasher);
+    let mut file = File::open(path)?;
+    let mut buf = [0; 64 * 1024];
+    loop {
+        let len = file.read(&mut buf)?;
+        if len == 0 {
+            break;
+        }
+        hasher.write(&buf[..len]);
+    }
     Ok(hasher.finish())
 }
 
 pub fn dependency_fingerprint(pkg: &Package) -> anyhow::Result<u64> {
     let mut hasher = StableHasher::default();
     ensure!(pkg.location.is_dir());
-    pkg.location.hash(&mut hasher);
-    modification_time(&pkg.location)?.hash(&mut hasher);
+    pkg.namespace.hash(&mut hasher);
+    pkg.name.hash(&mut hasher);
+    pkg.version.hash(&mut hasher);
+    file_fingerprint(&pkg.jar_absolute_path())?.hash(&mut hasher);
+    if let Some(descriptor) = resolve_descriptor(&pkg.location, pkg.maven_local.as_deref()) {
+        file_fingerprint(&descriptor)?.hash(&mut hasher);
+    }
     Ok(hasher.finish())
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::file_fingerprint;
+
+    #[test]
+    fn fingerprint_follows_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("A.kt");
+        let b = dir.path().join("B.kt");
+
+        std::fs::write(&a, "fun a() = 1").unwrap();
+        std::fs::write(&b, "fun a() = 1").unwrap();
+        assert_eq!(file_fingerprint(&a).unwrap(), file_fingerprint(&b).unwrap());
+
+        std::fs::write(&b, "fun a() = 2").unwrap();
+        assert_ne!(file_fingerprint(&a).unwrap(), file_fingerprint(&b).unwrap());
+    }
+}
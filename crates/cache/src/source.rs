@@ -0,0 +1,14 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// Fingerprint of a source file from the last successful build.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Source {
+    pub fingerprint: u64,
+    pub package: String,
+}
+
+impl Source {
+    pub fn new(fingerprint: u64, package: String) -> Self {
+        Source { fingerprint, package }
+    }
+}
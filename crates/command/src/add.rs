@@ -0,0 +1,94 @@
+use anyhow::Context;
+use async_std::task;
+use dependency::{Package, PackageKind};
+use http::client::{Client, DownloadResult};
+use http::retry::Policy;
+use manifest::config::BuildK;
+use manifest::edit::ManifestEditor;
+use manifest::Manifest;
+use util::buildk_output::{BuildkOutput, Failure};
+use util::PartialConclusion;
+
+use crate::fetch::Fetch;
+use crate::{failed, Command};
+
+pub(crate) struct Add<'a> {
+    buildk: &'a BuildK,
+    kind: PackageKind,
+}
+
+impl<'a> Command for Add<'a> {
+    type Item = String;
+
+    fn execute(&mut self, arg: Option<Self::Item>) -> BuildkOutput {
+        let mut output = BuildkOutput::new("add");
+        let Some(artifact) = arg else {
+            return failed("add", Failure::Config, "an artifact is required");
+        };
+
+        if let Err(err) = self.add(&artifact, &mut output) {
+            eprintln!("{err:#}");
+            output.conclude(PartialConclusion::FAILED).stderr(format!("{err:#}"));
+        }
+
+        output
+    }
+}
+
+impl<'a> Add<'a> {
+    pub fn new(buildk: &'a BuildK, kind: PackageKind) -> Add<'a> {
+        Add { buildk, kind }
+    }
+
+    /// Add the artifact to buildk.toml, then fetch it with the edited manifest.
+    fn add(&mut self, artifact: &str, output: &mut BuildkOutput) -> anyhow::Result<()> {
+        let manifest = self.buildk.manifest.as_ref().context("no buildk.toml found.")?;
+
+        let (namespace, name, version) = coordinates(artifact)?;
+        let pkg = match version {
+            Some(version) => Package::new(name, Some(namespace), version, self.kind.clone()),
+            None => self.latest(
+                Package::new(name, Some(namespace), "latest.release".into(), self.kind.clone())
+                    .maven_local(manifest.maven_local()),
+            )?,
+        };
+
+        let mut editor = ManifestEditor::open(&manifest.dir)?;
+        let section = Into::<String>::into(pkg.kind.clone()).to_lowercase();
+        match editor.add(&pkg)? {
+            Some(previous) => output.stdout(format!("updated {} from {previous} in [{section}]", pkg.coordinates())),
+            None => output.stdout(format!("added {} to [{section}]", pkg.coordinates())),
+        };
+        editor.save()?;
+
+        let buildk = BuildK {
+            manifest: Some(Manifest::try_from_dir(&manifest.dir)?),
+            ..self.buildk.clone()
+        };
+        output.apply(Fetch::new(&buildk, None, false, None).execute(None));
+        Ok(())
+    }
+
+    /// The latest release listed in the maven-metadata.xml of the repos.
+    fn latest(&self, pkg: Package) -> anyhow::Result<Package> {
+        let retry = self.buildk.manifest.as_ref().map(|it| Policy::from(&it.fetch)).unwrap_or_default();
+        let download = task::block_on(Client::new(retry).download_async(&pkg, self.buildk));
+
+        let resolved = pkg.clone().resolve_version();
+        match (resolved.is_dynamic(), download) {
+            (false, _) => Ok(resolved),
+            (true, DownloadResult::Failed(err)) => anyhow::bail!("no release of {} found: {err}", pkg.name),
+            (true, _) => anyhow::bail!("no release of {} found", pkg.name),
+        }
+    }
+}
+
+/// `<namespace>:<name>[:<version>]`
+fn coordinates(artifact: &str) -> anyhow::Result<(String, String, Option<String>)> {
+    let parts = artifact.split(':').collect::<Vec<_>>();
+    match parts.as_slice() {
+        [namespace, name] => Ok((namespace.to_string(), name.to_string(), None)),
+        [namespace, name, version] => Ok((namespace.to_string(), name.to_string(), Some(version.to_string()))),
+        _ => anyhow::bail!("unexpected artifact {artifact}, expected <namespace>:<name>[:<version>]"),
+    }
+}
@@ -1,19 +1,31 @@
-use std::path::{Path, PathBuf};
+use std::collections::BTreeSet;
+use std::fs::{create_dir_all, remove_file, write};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
 
+use anyhow::Context;
 use cache::cache::Cache;
+use cache::source::Source;
 use manifest::config::BuildK;
-use manifest::packages::Packages;
-use manifest::Manifest;
+use dependency::PackageKind;
+use manifest::workspace::{Member, Workspace};
+use process::java::Java;
 use process::kotlin::Kotlin;
-use util::buildk_output::BuildkOutput;
+use process::Process;
+use util::buildk_output::{BuildkOutput, Failure};
+use util::class_file;
+use util::diagnostic::Diagnostic;
+use util::hasher::StableHasher;
+use util::paths::all_files_recursive;
 use util::PartialConclusion;
 
-use crate::tree::Tree;
-use crate::{Set, Command};
+use crate::tree::{HeaderKt, Tree};
+use crate::{failed, Set, Command};
 
 pub (crate) struct Build<'a> {
     buildk: &'a BuildK,
     kotlin: &'a Kotlin<'a>,
+    java: &'a Java<'a>,
     tree: &'a Tree,
 }
 
@@ -22,89 +34,312 @@ impl <'a> Command for Build<'a> {
 
     fn execute(&mut self, arg: Option<Self::Item>) -> BuildkOutput {
         let mut output = BuildkOutput::new("build");
-        match arg {
+        let output = match arg {
             Some(Set::Src) => output.apply(self.build_src()),
             Some(Set::Test) => output.apply(self.build_test()),
             _ => output.apply(self.build_src()).apply(self.build_test())
+        };
+
+        if let Err(err) = self.write_sarif(output.get_diagnostics()) {
+            eprintln!("\rfailed to write the SARIF log: {err:#}");
         }
+        output
     }
 }
 
 impl <'a> Build<'_> {
-    pub fn new(buildk: &'a BuildK, kotlin: &'a Kotlin, tree: &'a Tree) -> Build<'a> {
-        Build { buildk, kotlin, tree }
+    pub fn new(buildk: &'a BuildK, kotlin: &'a Kotlin, java: &'a Java, tree: &'a Tree) -> Build<'a> {
+        Build { buildk, kotlin, java, tree }
+    }
+
+    /// The diagnostics of this build for code review tooling, an empty log when there were none.
+    fn write_sarif(&self, diagnostics: &[Diagnostic]) -> anyhow::Result<()> {
+        let manifest = self.buildk.manifest.as_ref().context("no buildk.toml found.")?;
+        let out_paths = manifest.project.out_paths();
+
+        create_dir_all(&out_paths.path)?;
+        let sarif = Diagnostic::sarif(diagnostics, &manifest.project.path);
+        write(&out_paths.sarif, serde_json::to_string_pretty(&sarif)?)?;
+        Ok(())
     }
 
     fn build_src(&mut self) -> BuildkOutput {
         let mut output = BuildkOutput::new("build src");
 
-        // FIXME
-        let manifest = <Option<Manifest> as Clone>::clone(&self.buildk.manifest)
-            .expect("no buildk.toml found.");
+        let Some(manifest) = self.buildk.manifest.clone() else {
+            return failed("build src", Failure::Config, "no buildk.toml found.");
+        };
 
-        let mut cache = Cache::load(&manifest.project.out_paths().cache);
-        let build_tree = self.tree.get_sorted_tree().expect("Failed to get sorted build tree");
-        let changed_files: Vec<&PathBuf> = build_tree.iter().filter(|file| cache.not_cached(file)).collect();
+        let out_paths = manifest.project.out_paths();
+        let build_tree = match self.tree.get_sorted_tree() {
+            Ok(build_tree) => build_tree,
+            Err(err) => return failed("build src", Failure::Config, format!("cyclic dependency detected: {err}")),
+        };
 
-        if changed_files.is_empty() {
+        // the cache is reloaded after compiling, kotlinc writes its own entries to the same file
+        let mut cache = Cache::load(&out_paths.cache);
+        // everything is compiled again when [kotlin.options] changed
+        let options = manifest.kotlin_options.fingerprint();
+        let full_build = !out_paths.src.is_dir() || cache.kotlinc_fingerprint() != options;
+        let changed_files: Vec<PathBuf> = build_tree
+            .iter()
+            .filter(|file| full_build || cache.not_cached(file))
+            .cloned()
+            .collect();
+        let removed_files: Vec<(PathBuf, Source)> = cache
+            .sources()
+            .iter()
+            .filter(|(file, _)| !build_tree.contains(file))
+            .map(|(file, source)| (file.clone(), source.clone()))
+            .collect();
+        drop(cache);
+
+        if changed_files.is_empty() && removed_files.is_empty() {
             return output.conclude(PartialConclusion::CACHED).to_owned();
         }
 
-        let cache_key = changed_files
+        let removed_packages = removed_files
             .iter()
-            .map(|src| cache::file_fingerprint(src).expect("Faile to create extra fingerprint"))
-            .reduce(|a, b| a + b)
-            .unwrap_or(0);
+            .map(|(_, source)| source.package.clone())
+            .collect::<Vec<_>>();
+        let affected_files = self.tree.affected(&changed_files, &removed_packages);
 
-        self.kotlin.builder()
-            .workdir(&manifest.project.path)
-            .target(&manifest.project.out_paths().src)
-            .sources(changed_files)
-            .cache_key(cache_key)
-            .compile(&mut output)
-            .to_owned()
+        let stale_sources = affected_files
+            .iter()
+            .filter_map(|file| HeaderKt::parse(file).ok())
+            .filter_map(|header| Some((header.package, file_name(&header.file)?)))
+            .chain(removed_files
+                .iter()
+                .filter_map(|(file, source)| Some((source.package.clone(), file_name(file)?))))
+            .collect::<BTreeSet<_>>();
+
+        if let Err(err) = delete_stale_classes(&out_paths.src, &stale_sources) {
+            return output
+                .conclude(PartialConclusion::FAILED)
+                .stderr(format!("failed to delete stale classes: {err}"))
+                .to_owned();
+        }
+
+        // their classes are gone, when kotlinc or javac fails they are compiled again even if reverted
+        let mut cache = Cache::load(&out_paths.cache);
+        affected_files.iter().for_each(|file| cache.remove_source(file));
+        drop(cache);
+
+        if !affected_files.is_empty() {
+            let module_outputs = manifest.module_outputs();
+            let compile_libs = manifest.classpath(&[PackageKind::Compile]);
+            let mut classpath = vec![&out_paths.src];
+            classpath.extend(module_outputs.iter());
+            classpath.extend(compile_libs.iter());
+
+            // kotlinc only reads the Java sources to resolve their symbols, javac compiles them against its classes
+            if affected_files.iter().any(|file| is_kotlin(file)) {
+                let mut compiled = self.kotlin.builder()
+                    .workdir(&manifest.project.path)
+                    .classpath(classpath.clone())
+                    .target(&out_paths.src)
+                    .sources(affected_files.iter().collect())
+                    .no_cache()
+                    .compile(&mut output);
+
+                if compiled.conclusion() == PartialConclusion::FAILED {
+                    return compiled.fail(Failure::Compile).to_owned();
+                }
+            }
+
+            let java_files = affected_files.iter().filter(|file| !is_kotlin(file)).collect::<Vec<_>>();
+            if !java_files.is_empty() {
+                let compiled = self.java.builder()
+                    .workdir(&manifest.project.path)
+                    .classpath(classpath)
+                    .target(&out_paths.src)
+                    .sources(java_files)
+                    .no_cache()
+                    .compile(&mut BuildkOutput::new("build src"));
+
+                output.apply(compiled);
+                if output.conclusion() == PartialConclusion::FAILED {
+                    return output.fail(Failure::Compile).to_owned();
+                }
+            }
+        }
+
+        let mut cache = Cache::load(&out_paths.cache);
+        cache.set_kotlinc_fingerprint(options);
+        removed_files.iter().for_each(|(file, _)| cache.remove_source(file));
+        // the stamps of test sources are kept, build test fingerprints those
+        cache.retain_stamps(|path| !path.starts_with(&manifest.project.src) || build_tree.iter().any(|file| file == path));
+        affected_files
+            .iter()
+            .filter_map(|file| HeaderKt::parse(file).ok())
+            .for_each(|header| {
+                if let Ok(fingerprint) = cache.fingerprint(&header.file) {
+                    cache.insert_source(header.file.clone(), Source::new(fingerprint, header.package));
+                }
+            });
+
+        output.conclude(PartialConclusion::SUCCESS).to_owned()
     }
 
     fn build_test(&mut self) -> BuildkOutput {
         let mut output = BuildkOutput::new("build test");
-        // FIXME
-        let manifest = <Option<Manifest> as Clone>::clone(&self.buildk.manifest)
-            .expect("no buildk.toml found.");
+        let Some(manifest) = self.buildk.manifest.clone() else {
+            return failed("build test", Failure::Config, "no buildk.toml found.");
+        };
 
         if !manifest.project.test.is_dir(){
             return output.to_owned()
         }
 
-        let test_deps: Packages = manifest.test_deps;
-
-        let project_test_libs = test_deps.filter_cached()
-            .iter()
-            .map(|pkg| pkg.location.join("pkg").with_extension("jar"))
-            .collect::<Vec<PathBuf>>();
+        let project_test_libs = manifest.classpath(&[PackageKind::Compile, PackageKind::Test]);
 
         // let test_libs = self.kotlin.test_libs();
         let output_paths = &manifest.project.out_paths();
+        let module_outputs = manifest.module_outputs();
         let mut classpath = vec![&output_paths.src];
+        classpath.extend(module_outputs.iter());
         classpath.extend(project_test_libs.iter());
         // classpath.extend(test_libs.iter());
 
-        self.kotlin.builder()
+        // the arguments stay the same when a test changes, the content of every test is part of the key
+        let test_files = all_files_recursive(vec![], manifest.project.test.clone()).unwrap_or_default();
+        let mut cache = Cache::load(&output_paths.cache);
+        let mut hasher = StableHasher::default();
+        test_files.iter().for_each(|file| (file, cache.fingerprint(file).ok()).hash(&mut hasher));
+        drop(cache);
+        let test_key = hasher.finish();
+
+        let mut compiled = self.kotlin.builder()
             .workdir(&manifest.project.path)
             .sources(vec![&manifest.project.test])
+            .classpath(classpath.clone())
+            .target(&output_paths.test)
+            .cache_key(test_key)
+            .compile(&mut output);
+
+        let java_files = test_files
+            .into_iter()
+            .filter(|file| file.extension().unwrap_or_default() == "java")
+            .collect::<Vec<_>>();
+        if compiled.conclusion() == PartialConclusion::FAILED {
+            return compiled.fail(Failure::Compile).to_owned();
+        }
+        if java_files.is_empty() {
+            return compiled;
+        }
+
+        classpath.insert(1, &output_paths.test);
+        let compiled = self.java.builder()
+            .workdir(&manifest.project.path)
+            .sources(java_files.iter().collect())
             .classpath(classpath)
-            .target(&manifest.project.out_paths().test)
-            .compile(&mut output)
-            .to_owned()
+            .target(&output_paths.test)
+            .cache_key(test_key)
+            .compile(&mut BuildkOutput::new("build test"));
+
+        output.apply(compiled);
+        if output.conclusion() == PartialConclusion::FAILED {
+            output.fail(Failure::Compile);
+        }
+        output
     }
 }
 
+/// Build the workspace members in dependency order, stopping at the first failing member.
+pub(crate) fn build_workspace(buildk: &BuildK, workspace: &Workspace, module: Option<&str>, set: Set) -> BuildkOutput {
+    let mut output = BuildkOutput::new("build");
+
+    let members = match workspace.sorted_members(module) {
+        Ok(members) => members,
+        Err(err) => return failed("build", Failure::Config, format!("{err:#}")),
+    };
+
+    let mut rebuilt: Vec<&Member> = vec![];
+
+    for member in members {
+        let member_buildk = BuildK {
+            home: buildk.home.clone(),
+            manifest: Some(member.manifest.clone()),
+            manifest_error: None,
+            offline: buildk.offline,
+            daemon: buildk.daemon,
+        };
+
+        // classes from a rebuilt module may have changed its API, recompile everything depending on it
+        if rebuilt.iter().any(|module| member.depends_on(module)) {
+            Cache::load(&member.manifest.project.out_paths().cache).invalidate();
+        }
+
+        let result = match (Kotlin::new(&member_buildk), Java::new(&member_buildk), Tree::new(&member_buildk)) {
+            (Ok(kotlin), Ok(java), Ok(tree)) => Build::new(&member_buildk, &kotlin, &java, &tree).execute(Some(set)),
+            (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => failed("build", Failure::Config, format!("{err:#}")),
+        };
+
+        println!("\r{:<6} {}", result.conclusion().color_symbol(), member.name);
+
+        if result.conclusion() == PartialConclusion::FAILED {
+            let stderr = result.get_stderr().unwrap_or_default();
+            return output
+                .apply(result)
+                .stderr(format!("{}: {}", member.name, stderr))
+                .to_owned();
+        }
+
+        if result.conclusion() == PartialConclusion::SUCCESS {
+            rebuilt.push(member);
+        }
+
+        output.apply(result);
+    }
+
+    output
+}
+
 trait IsCached {
     fn not_cached(&mut self, file: &Path) -> bool;
 }
 
 impl IsCached for Cache {
-    fn not_cached(&mut self, _file: &Path) -> bool {
-        //!matches!(self.cache_file(file), Ok(PartialConclusion::CACHED))
-        true
+    fn not_cached(&mut self, file: &Path) -> bool {
+        let recorded = self.source(file).map(|source| source.fingerprint);
+        match (recorded, self.fingerprint(file)) {
+            (Some(recorded), Ok(fingerprint)) => recorded != fingerprint,
+            _ => true,
+        }
     }
 }
+
+fn is_kotlin(file: &Path) -> bool {
+    file.extension().unwrap_or_default() == "kt"
+}
+
+fn file_name(file: &Path) -> Option<String> {
+    file.file_name().map(|name| name.to_string_lossy().to_string())
+}
+
+/// Delete every class file compiled from one of the given (package, source file name) pairs.
+fn delete_stale_classes(out: &Path, sources: &BTreeSet<(String, String)>) -> anyhow::Result<()> {
+    if sources.is_empty() {
+        return Ok(());
+    }
+
+    let classes = all_files_recursive(vec![], out.to_path_buf())?
+        .into_iter()
+        .filter(|file| file.extension().unwrap_or_default() == "class");
+
+    for class in classes {
+        let package = class
+            .parent()
+            .and_then(|dir| dir.strip_prefix(out).ok())
+            .map(|dir| dir.to_string_lossy().replace(MAIN_SEPARATOR, "."))
+            .unwrap_or_default();
+
+        if let Some(source_file) = class_file::source_file(&class)? {
+            if sources.contains(&(package, source_file)) {
+                remove_file(&class)?;
+            }
+        }
+    }
+
+    Ok(())
+}
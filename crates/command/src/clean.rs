@@ -3,7 +3,7 @@ use std::path::Path;
 
 use cache::cache::Cache;
 use manifest::{config::BuildK, Manifest};
-use util::buildk_output::BuildkOutput;
+use util::buildk_output::{BuildkOutput, Failure};
 use util::PartialConclusion;
 
 use crate::{Command, CleanSet};
@@ -19,13 +19,15 @@ impl <'a> Command for Clean<'a> {
 
     fn execute(&mut self, arg: Option<Self::Item>) -> BuildkOutput {
         let mut output = BuildkOutput::new("clean");
-        let arg = arg.expect("arg should have default value == All");
-
-        match arg {
-            CleanSet::Src => self.clean_src(&mut output),
-            CleanSet::Test => self.clean_test(&mut output),
-            CleanSet::Release => self.clean_release(&mut output),
-            CleanSet::All => self.clean_all(&mut output)
+        let Some(manifest) = self.buildk.manifest.clone() else {
+            return crate::failed("clean", Failure::Config, "no buildk.toml found.");
+        };
+
+        match arg.unwrap_or(CleanSet::All) {
+            CleanSet::Src => self.clean_src(&manifest, &mut output),
+            CleanSet::Test => self.clean_test(&manifest, &mut output),
+            CleanSet::Release => self.clean_release(&manifest, &mut output),
+            CleanSet::All => self.clean_all(&manifest, &mut output)
         }
     }
 }
@@ -35,55 +37,35 @@ impl <'a> Clean<'_> {
         Clean { buildk }
     }
 
-    fn clean_src(&mut self, output: &mut BuildkOutput) -> BuildkOutput {
-        // FIXME
-        let manifest = <Option<Manifest> as Clone>::clone(&self.buildk.manifest)
-            .expect("no buildk.toml found.");
-
+    fn clean_src(&mut self, manifest: &Manifest, output: &mut BuildkOutput) -> BuildkOutput {
         let path = &manifest.project.out_paths().src;
-        self.delete(output, path)
+        self.delete(manifest, output, path)
     }
 
-    fn clean_test(&mut self, output: &mut BuildkOutput) -> BuildkOutput {
-        // FIXME
-        let manifest = <Option<Manifest> as Clone>::clone(&self.buildk.manifest)
-            .expect("no buildk.toml found.");
-
+    fn clean_test(&mut self, manifest: &Manifest, output: &mut BuildkOutput) -> BuildkOutput {
         let path = &manifest.project.out_paths().test;
-        self.delete(output, path)
+        self.delete(manifest, output, path)
     }
 
-    fn clean_release(&mut self, output: &mut BuildkOutput) -> BuildkOutput {
-        // FIXME
-        let manifest = <Option<Manifest> as Clone>::clone(&self.buildk.manifest)
-            .expect("no buildk.toml found.");
-
+    fn clean_release(&mut self, manifest: &Manifest, output: &mut BuildkOutput) -> BuildkOutput {
         let path = &manifest.project.out_paths().release;
-        self.delete(output, path)
+        self.delete(manifest, output, path)
     }
 
-    fn clean_all(&mut self, output: &mut BuildkOutput) -> BuildkOutput {
-        // FIXME
-        let manifest = <Option<Manifest> as Clone>::clone(&self.buildk.manifest)
-            .expect("no buildk.toml found.");
-
+    fn clean_all(&mut self, manifest: &Manifest, output: &mut BuildkOutput) -> BuildkOutput {
         let path = &manifest.project.out_paths().path;
-        self.delete(output, path) 
+        self.delete(manifest, output, path) 
     }
 
-    fn delete(&mut self, output: &mut BuildkOutput, out_dir: &Path) -> BuildkOutput {
+    fn delete(&mut self, manifest: &Manifest, output: &mut BuildkOutput, out_dir: &Path) -> BuildkOutput {
         match remove_dir_all(out_dir) {
-            Ok(_) => self.cleaned(output, out_dir),
-            Err(e) if e.to_string() == *OS_2_ERROR => self.cleaned(output, out_dir),
+            Ok(_) => self.cleaned(manifest, output, out_dir),
+            Err(e) if e.to_string() == *OS_2_ERROR => self.cleaned(manifest, output, out_dir),
             Err(e) => self.failed(output, out_dir, e)
         }
     }
 
-    fn cleaned(&mut self, output: &mut BuildkOutput, dir: &Path) -> BuildkOutput {
-        // FIXME
-        let manifest = <Option<Manifest> as Clone>::clone(&self.buildk.manifest)
-            .expect("no buildk.toml found.");
-
+    fn cleaned(&mut self, manifest: &Manifest, output: &mut BuildkOutput, dir: &Path) -> BuildkOutput {
         let mut cache = Cache::load(&manifest.project.out_paths().cache);
         cache.invalidate();
 
@@ -0,0 +1,104 @@
+use manifest::config::BuildK;
+use process::daemon::CompileServer;
+use util::buildk_output::BuildkOutput;
+use util::PartialConclusion;
+
+use crate::{Command, DaemonAction};
+
+pub(crate) struct Daemon<'a> {
+    buildk: &'a BuildK,
+}
+
+impl<'a> Command for Daemon<'a> {
+    type Item = DaemonAction;
+
+    fn execute(&mut self, arg: Option<Self::Item>) -> BuildkOutput {
+        let mut output = BuildkOutput::new("daemon");
+
+        let result = match arg.unwrap_or(DaemonAction::Status) {
+            DaemonAction::Status => self.status(),
+            DaemonAction::Stop => self.stop(),
+        };
+
+        match result {
+            Ok(report) => output.conclude(PartialConclusion::SUCCESS).stdout(report),
+            Err(err) => {
+                eprintln!("{err:#}");
+                output.conclude(PartialConclusion::FAILED).stderr(format!("{err:#}"))
+            }
+        };
+
+        output
+    }
+}
+
+impl<'a> Daemon<'a> {
+    pub fn new(buildk: &'a BuildK) -> Daemon<'a> {
+        Daemon { buildk }
+    }
+
+    /// One line per running compile server.
+    fn status(&self) -> anyhow::Result<String> {
+        let mut running = vec![];
+        for server in CompileServer::all(&self.buildk.home) {
+            let Some(status) = server.status()? else {
+                continue;
+            };
+            let get = |key: &str| status.get(key).map(String::as_str).unwrap_or("?");
+            let secs = |key: &str| get(key).parse::<u64>().map(duration).unwrap_or_else(|_| "?".into());
+
+            running.push(format!(
+                "{}\n  pid {}, up {}, idle {} of {}, {} compilations",
+                get("kotlin"),
+                get("pid"),
+                secs("uptime"),
+                secs("idle"),
+                secs("idle_timeout"),
+                get("compilations"),
+            ));
+        }
+
+        match running.is_empty() {
+            true => Ok("no compile server running".to_owned()),
+            false => Ok(running.join("\n")),
+        }
+    }
+
+    fn stop(&self) -> anyhow::Result<String> {
+        let mut stopped = 0;
+        for server in CompileServer::all(&self.buildk.home) {
+            if server.stop()? {
+                stopped += 1;
+            }
+        }
+
+        Ok(match stopped {
+            0 => "no compile server running".to_owned(),
+            1 => "stopped 1 compile server".to_owned(),
+            n => format!("stopped {n} compile servers"),
+        })
+    }
+}
+
+/// e.g. `1h 5m`, `5m` or `42s`
+fn duration(secs: u64) -> String {
+    match (secs / 3600, secs / 60 % 60) {
+        (0, 0) => format!("{secs}s"),
+        (0, minutes) => format!("{minutes}m"),
+        (hours, 0) => format!("{hours}h"),
+        (hours, minutes) => format!("{hours}h {minutes}m"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::duration;
+
+    #[test]
+    fn durations() {
+        assert_eq!(duration(42), "42s");
+        assert_eq!(duration(300), "5m");
+        assert_eq!(duration(7200), "2h");
+        assert_eq!(duration(3900), "1h 5m");
+    }
+}
@@ -1,8 +1,8 @@
 use dependency::{Package, PackageKind};
 use manifest::config::BuildK;
-use util::buildk_output::BuildkOutput;
+use util::buildk_output::{BuildkOutput, Failure};
 
-use crate::Command;
+use crate::{failed, Command};
 
 #[allow(dead_code)]
 pub(crate) struct DepPath<'a> {
@@ -13,15 +13,17 @@ impl<'a> Command for DepPath<'a> {
     type Item = String;
 
     fn execute(&mut self, arg: Option<Self::Item>) -> BuildkOutput {
-        let output = BuildkOutput::new("config");
-        let arg = arg.expect("missing arg <namespace>..<name>=<version>");
+        let mut output = BuildkOutput::new("config");
+        let Some(arg) = arg else {
+            return failed("path", Failure::Config, "an artifact <namespace>:<name>:<version> is required");
+        };
         let artifact = arg.split(":").collect::<Vec<&str>>();
 
         let (name, namespace, version) = match artifact.len() {
             2 => (
-                artifact[1].to_string(),
+                artifact[0].to_string(),
                 None,
-                artifact[2].to_string(),
+                artifact[1].to_string(),
                 ),
             3 => (
                 artifact[1].to_string(),
@@ -30,7 +32,10 @@ impl<'a> Command for DepPath<'a> {
                 ),
             _ => {
                 eprintln!("Package must be defined by <namespace>:<name>:<version>");
-                panic!("Package must be defined by <namespace>:<name>:<version>");
+                return output
+                    .fail(Failure::Config)
+                    .stderr("Package must be defined by <namespace>:<name>:<version>".into())
+                    .to_owned();
             }
         };
         
@@ -1,22 +1,21 @@
-use dependency::Package;
-use futures::future::BoxFuture;
-use futures::FutureExt;
+use dependency::{Exclusion, Package, PackageKind};
 use manifest::config::BuildK;
-use manifest::Manifest;
 use termtree::Tree;
 
-use util::buildk_output::BuildkOutput;
+use util::buildk_output::{BuildkOutput, Failure};
 use util::colorize::{Color, Colorize, Colors};
-use util::{PartialConclusion, DEBUG};
+use util::PartialConclusion;
 
-use crate::Command;
+use crate::export::Graph;
+use crate::{failed, Command, GraphFormat};
 
 pub(crate) struct Deps<'a> {
     buildk: &'a BuildK,
+    format: GraphFormat,
 }
 
 fn termtree_display(status: &str, pkg: &Package) -> String {
-    format!("{}:{}:{} {}", &pkg.namespace.clone().unwrap(), pkg.name, pkg.version, status)
+    format!("{}:{}:{} {}", &pkg.namespace.clone().unwrap_or_default(), pkg.name, pkg.version, status)
 }
 
 fn termtree_status(dep: &Package) -> String {
@@ -46,9 +45,14 @@ impl Counter {
     }
 }
 
+fn termtree_pruned(pkg: &Package) -> String {
+    format!("{}:{}:{} {}", &pkg.namespace.clone().unwrap_or_default(), pkg.name, pkg.version, "(excluded)").as_gray()
+}
+
 pub fn build_termtree(
     pkg: Package,
     mut traversed: Vec<Package>,
+    excluded: &[Exclusion],
     depth: usize,
     limit: usize,
 ) -> anyhow::Result<(Tree<String>, Vec<Package>, Counter)> {
@@ -57,10 +61,6 @@ pub fn build_termtree(
         false => traversed.push(pkg.clone()),
     }
 
-    if pkg.name.to_string().contains("-bom") {
-        anyhow::bail!("bom not supported yet");
-    }
-
     let mut counter_acc = match pkg.is_cached() {
         true => Counter::hit(),
         false => Counter::miss(),
@@ -71,16 +71,28 @@ pub fn build_termtree(
     let color = Color::get_index(depth);
     let label = display.colorize(&color).to_string();
 
+    let excluded = excluded.iter().chain(pkg.exclusions.iter()).cloned().collect::<Vec<_>>();
+    let mut pruned_acc = vec![];
+
     if depth < limit {
         let (tree, traversed) = pkg
             .transitives()
             .into_iter()
+            .filter(|it| it.kind != PackageKind::Pom)
             .filter(|it| !traversed.contains(it))
+            .filter(|it| {
+                let pruned = excluded.iter().any(|exclusion| exclusion.matches(it));
+                if pruned {
+                    pruned_acc.push(Tree::new(termtree_pruned(it)));
+                }
+                !pruned
+            })
             .fold(
                 (Tree::new(label), traversed.clone()),
                 |(mut tree_acc, trav_acc), entry| match build_termtree(
-                    entry,
+                    entry.clone(),
                     trav_acc.clone(),
+                    &excluded,
                     depth + 1,
                     limit,
                 ) {
@@ -92,47 +104,46 @@ pub fn build_termtree(
                     Err(_) => (tree_acc, trav_acc),
                 },
             );
-        Ok((tree, traversed, counter_acc))
+        Ok((tree.with_leaves(pruned_acc), traversed, counter_acc))
     } else {
         Ok((Tree::new(label), traversed, counter_acc))
     }
 }
 
-pub fn acc_transitive_unique(pkg: Package, mut traversed: Vec<Package>) -> Vec<Package> {
-    match traversed.contains(&pkg) {
-        true => return traversed,
-        false => traversed.push(pkg.clone()),
-    }
-
-    let traversed = pkg
-        .transitives()
-        .into_iter()
-        .filter(|it| !traversed.contains(it))
-        .filter(|it| it.jar_absolute_path().exists())
-        .fold(traversed.clone(), |acc, entry| {
-            acc_transitive_unique(entry, acc)
-        });
-
-    traversed
-}
-
 impl<'a> Command for Deps<'a> {
     type Item = usize;
 
     fn execute(&mut self, arg: Option<Self::Item>) -> BuildkOutput {
         let mut output = BuildkOutput::new("deps");
 
-        // FIXME
-        let manifest = <Option<Manifest> as Clone>::clone(&self.buildk.manifest)
-            .expect("no buildk.toml found.");
+        let Some(manifest) = self.buildk.manifest.clone() else {
+            return failed("deps", Failure::Config, "no buildk.toml found.");
+        };
+
+        // printed as is, so `buildk -q deps --format json` is nothing but the document
+        let export = match self.format {
+            GraphFormat::Tree => None,
+            GraphFormat::Json => Some(Graph::resolve(&manifest).json()),
+            GraphFormat::Dot => Some(Graph::resolve(&manifest).dot()),
+            GraphFormat::Cyclonedx => Some(Graph::resolve(&manifest).cyclonedx()),
+        };
+        if let Some(export) = export {
+            println!("{export}");
+            return output.conclude(PartialConclusion::SUCCESS).to_owned();
+        }
 
         let limit = arg.unwrap_or(999);
 
+        let kinds = [PackageKind::Compile, PackageKind::Runtime, PackageKind::Test];
         let mut traversed = vec![];
         let mut counter_acc = Counter { hit: 0, miss: 0 };
-        for dep in manifest.all_packages.pkgs.iter() {
-            let (tree, newly_traversed, counter) =
-                build_termtree(dep.clone(), traversed.clone(), 0, limit).unwrap();
+        for dep in manifest.roots(&kinds).iter() {
+            // a root declared twice, e.g. for compile and test, is only shown once
+            let Ok((tree, newly_traversed, counter)) =
+                build_termtree(dep.clone(), traversed.clone(), &[], 0, limit)
+            else {
+                continue;
+            };
             traversed = newly_traversed;
             counter_acc.apply(counter);
             print!("{}", tree);
@@ -148,6 +159,18 @@ impl<'a> Command for Deps<'a> {
             println!("");
         }
 
+        let resolution = manifest.resolve(&kinds);
+        for conflict in resolution.conflicts.iter() {
+            println!(
+                "\r{} {}:{}:{} -> {}",
+                "conflict".as_yellow(),
+                conflict.rejected.namespace.clone().unwrap_or_default(),
+                conflict.rejected.name,
+                conflict.rejected.version,
+                conflict.selected.version,
+            );
+        }
+
         match lsp::update_classpath(self.buildk) {
             Ok(_) => output.conclude(PartialConclusion::SUCCESS),
             Err(err) => output
@@ -161,72 +184,17 @@ impl<'a> Command for Deps<'a> {
 }
 
 impl<'a> Deps<'a> {
-    pub fn new(buildk: &'a BuildK) -> Deps<'a> {
-        Deps { buildk }
-    }
-}
-
-fn status(pkg: &Package) -> &str {
-    match pkg.is_cached() {
-        true => "[cached]",
-        false => "[missing]",
-    }
-}
-
-fn display(status: &str, pkg: &Package, depth: usize) -> String {
-    format!(
-        "\r{:>depth$}{:<14}{}:{}",
-        "",
-        status,
-        pkg.name,
-        pkg.version,
-        depth = depth * 2
-    )
-}
-
-pub fn find_dependent_deps(
-    pkgs: Vec<Package>,
-    mut traversed: Vec<Package>,
-    depth: usize,
-    print: bool,
-) -> BoxFuture<'static, Vec<Package>> {
-    async move {
-        if pkgs.is_empty() {
-            return traversed;
-        }
-
-        pkgs.iter().for_each(|dep| {
-            let status = status(dep);
-            let display = display(status, dep, depth);
-            let color = Color::get_index(depth);
-            let stdout = display.colorize(&color).to_string();
-            if DEBUG {
-                println!("{}", stdout);
-            }
-
-            if !traversed.contains(dep) {
-                traversed.push(dep.clone());
-            }
-        });
-
-        let transitives = pkgs
-            .iter()
-            .flat_map(|it| it.transitives())
-            .filter(|it| !traversed.contains(it))
-            .collect::<Vec<_>>();
-
-        find_dependent_deps(transitives, traversed, depth + 1, print).await
+    pub fn new(buildk: &'a BuildK, format: GraphFormat) -> Deps<'a> {
+        Deps { buildk, format }
     }
-    .boxed()
 }
 
 mod lsp {
     use std::os::unix::fs::OpenOptionsExt;
 
     use anyhow::Context;
-    use manifest::{config::BuildK, Manifest};
-
-    use crate::deps::acc_transitive_unique;
+    use dependency::PackageKind;
+    use manifest::config::BuildK;
 
     /**
      * This function is used to update the classpath for the kotlin language server.
@@ -237,19 +205,15 @@ mod lsp {
 
         // TODO: add transitive dependencies to kls classpath
 
-        // FIXME
-        let manifest =
-            <Option<Manifest> as Clone>::clone(&buildk.manifest).expect("no buildk.toml found.");
+        let manifest = buildk.manifest.as_ref().context("no buildk.toml found.")?;
 
         let kls_classpath = home::home_dir()
             .map(|home| home.join(".config"))
-            .expect("Failed to get home dir")
+            .context("Failed to get home dir")?
             .join("kotlin-language-server")
             .join("classpath"); // see https://github.com/fwcd/kotlin-language-server?tab=readme-ov-file#figuring-out-the-dependencies
 
-        let pkgs = manifest.all_packages.pkgs
-            .iter()
-            .fold(vec![], |acc, pkg| acc_transitive_unique(pkg.clone(), acc));
+        let jars = manifest.classpath(&[PackageKind::Compile, PackageKind::Runtime, PackageKind::Test]);
 
         /*
                 let classpath = manifest
@@ -260,9 +224,9 @@ mod lsp {
                     .join(":");
         */
 
-        let classpath = pkgs
+        let classpath = jars
             .iter()
-            .map(|pkg| pkg.jar_absolute_path().display().to_string())
+            .map(|jar| jar.display().to_string())
             .collect::<Vec<_>>()
             .join(":");
 
@@ -0,0 +1,221 @@
+use dependency::{resolver, Package, PackageKind};
+use manifest::Manifest;
+use serde_json::{json, Value};
+
+/// The resolved dependency graph, one node per selected package.
+pub(crate) struct Graph {
+    pub name: String,
+    pub roots: Vec<Package>,
+    pub nodes: Vec<Node>,
+    /// From a selected package to the selected version of a package it requests, with the requested version.
+    pub edges: Vec<(Package, Package, String)>,
+}
+
+pub(crate) struct Node {
+    pub pkg: Package,
+    pub file: Option<String>,
+    pub sha256: Option<String>,
+}
+
+impl Graph {
+    pub fn resolve(manifest: &Manifest) -> Graph {
+        let kinds = [PackageKind::Compile, PackageKind::Runtime, PackageKind::Test];
+        let selected = manifest
+            .resolve(&kinds)
+            .selected
+            .into_iter()
+            .filter(|pkg| pkg.kind != PackageKind::Pom)
+            .collect::<Vec<_>>();
+        let winner = resolver::winner(&selected);
+        let roots = manifest.roots(&kinds).iter().map(&winner).collect::<Vec<_>>();
+
+        // edges into modules left out of the resolution, e.g. excluded ones, are dropped
+        let edges = resolver::edges(&roots, &selected, Package::transitives)
+            .into_iter()
+            .flat_map(|(from, requested)| {
+                let winner = &winner;
+                requested.into_iter().map(move |it| (from.clone(), winner(&it), it.version))
+            })
+            .filter(|(_, to, _)| selected.contains(to))
+            .collect();
+
+        let nodes = selected
+            .into_iter()
+            .map(|pkg| {
+                let jar = pkg.jar_absolute_path();
+                Node {
+                    file: jar.exists().then(|| jar.display().to_string()),
+                    sha256: util::hasher::sha256(&jar).ok(),
+                    pkg,
+                }
+            })
+            .collect();
+
+        let name = manifest.dir.file_name().map(|it| it.to_string_lossy().to_string()).unwrap_or_default();
+
+        Graph { name, roots, nodes, edges }
+    }
+
+    pub fn json(&self) -> String {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| {
+                json!({
+                    "id": node.pkg.coordinates(),
+                    "group": node.pkg.namespace,
+                    "name": node.pkg.name,
+                    "version": node.pkg.version,
+                    "scope": scope(&node.pkg),
+                    "file": node.file,
+                    "sha256": node.sha256,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let edges = self
+            .edges
+            .iter()
+            .map(|(from, to, requested)| json!({ "from": from.coordinates(), "to": to.coordinates(), "requested": requested }))
+            .collect::<Vec<_>>();
+
+        let roots = self.roots.iter().map(Package::coordinates).collect::<Vec<_>>();
+
+        pretty(json!({ "roots": roots, "nodes": nodes, "edges": edges }))
+    }
+
+    /// Graphviz, e.g. `buildk -q deps --format dot | dot -Tsvg > deps.svg`
+    pub fn dot(&self) -> String {
+        let mut lines = vec!["digraph dependencies {".to_owned(), "  node [shape=box];".to_owned()];
+
+        for node in self.nodes.iter() {
+            let id = node.pkg.coordinates();
+            let style = match self.roots.contains(&node.pkg) {
+                true => ", style=bold",
+                false => "",
+            };
+            lines.push(format!("  \"{id}\" [label=\"{id}\\n{}\"{style}];", scope(&node.pkg)));
+        }
+
+        for (from, to, requested) in self.edges.iter() {
+            let label = match &to.version == requested {
+                true => String::new(),
+                false => format!(" [label=\"{requested}\"]"),
+            };
+            lines.push(format!("  \"{}\" -> \"{}\"{label};", from.coordinates(), to.coordinates()));
+        }
+
+        lines.push("}".to_owned());
+        lines.join("\n")
+    }
+
+    /// A CycloneDX 1.5 SBOM, test dependencies are listed with scope excluded.
+    pub fn cyclonedx(&self) -> String {
+        let components = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let mut component = json!({
+                    "type": "library",
+                    "bom-ref": purl(&node.pkg),
+                    "group": node.pkg.namespace,
+                    "name": node.pkg.name,
+                    "version": node.pkg.version,
+                    "scope": match node.pkg.kind {
+                        PackageKind::Test => "excluded",
+                        _ => "required",
+                    },
+                    "purl": purl(&node.pkg),
+                });
+                if let Some(sha256) = &node.sha256 {
+                    component["hashes"] = json!([{ "alg": "SHA-256", "content": sha256 }]);
+                }
+                component
+            })
+            .collect::<Vec<_>>();
+
+        let application = self.name.clone();
+        let dependencies = std::iter::once((application.clone(), self.roots.iter().collect::<Vec<_>>()))
+            .chain(self.nodes.iter().map(|node| {
+                let depends_on = self.edges.iter().filter(|(from, _, _)| from == &node.pkg).map(|(_, to, _)| to).collect();
+                (purl(&node.pkg), depends_on)
+            }))
+            .map(|(reference, depends_on)| {
+                let mut depends_on = depends_on.into_iter().map(purl).collect::<Vec<_>>();
+                depends_on.sort();
+                depends_on.dedup();
+                json!({ "ref": reference, "dependsOn": depends_on })
+            })
+            .collect::<Vec<_>>();
+
+        pretty(json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "version": 1,
+            "metadata": {
+                "tools": { "components": [{ "type": "application", "name": "buildk", "version": env!("CARGO_PKG_VERSION") }] },
+                "component": { "type": "application", "bom-ref": application, "name": self.name },
+            },
+            "components": components,
+            "dependencies": dependencies,
+        }))
+    }
+}
+
+fn scope(pkg: &Package) -> String {
+    Into::<String>::into(pkg.kind.clone()).to_lowercase()
+}
+
+/// `pkg:maven/<group>/<artifact>@<version>`
+fn purl(pkg: &Package) -> String {
+    match &pkg.namespace {
+        Some(namespace) => format!("pkg:maven/{namespace}/{}@{}", pkg.name, pkg.version),
+        None => format!("pkg:maven/{}@{}", pkg.name, pkg.version),
+    }
+}
+
+fn pretty(value: Value) -> String {
+    serde_json::to_string_pretty(&value).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use dependency::{Package, PackageKind};
+
+    use super::{Graph, Node};
+
+    fn graph() -> Graph {
+        let pkg = |name: &str, version: &str, kind| Package::new(name.into(), Some("org.example".into()), version.into(), kind);
+        let node = |pkg: Package| Node { pkg, file: None, sha256: Some("abc".into()) };
+
+        let (app, lib, junit) = (pkg("app", "1.0", PackageKind::Compile), pkg("lib", "2.0", PackageKind::Compile), pkg("junit", "5.0", PackageKind::Test));
+        Graph {
+            name: "demo".into(),
+            roots: vec![app.clone(), junit.clone()],
+            nodes: vec![node(app.clone()), node(lib.clone()), node(junit)],
+            edges: vec![(app, lib, "1.0".into())],
+        }
+    }
+
+    #[test]
+    fn exports_dot() {
+        let dot = graph().dot();
+
+        assert!(dot.contains("  \"org.example:app:1.0\" [label=\"org.example:app:1.0\\ncompile\", style=bold];"));
+        assert!(dot.contains("  \"org.example:app:1.0\" -> \"org.example:lib:2.0\" [label=\"1.0\"];"));
+    }
+
+    #[test]
+    fn exports_json_and_cyclonedx() {
+        let json = serde_json::from_str::<serde_json::Value>(&graph().json()).unwrap();
+        assert_eq!(json["edges"][0]["requested"], "1.0");
+        assert_eq!(json["nodes"][2]["scope"], "test");
+
+        let bom = serde_json::from_str::<serde_json::Value>(&graph().cyclonedx()).unwrap();
+        assert_eq!(bom["components"][1]["purl"], "pkg:maven/org.example/lib@2.0");
+        assert_eq!(bom["components"][1]["hashes"][0]["content"], "abc");
+        assert_eq!(bom["components"][2]["scope"], "excluded");
+        assert_eq!(bom["dependencies"][0]["dependsOn"][1], "pkg:maven/org.example/junit@5.0");
+        assert_eq!(bom["dependencies"][1]["dependsOn"][0], "pkg:maven/org.example/lib@2.0");
+    }
+}
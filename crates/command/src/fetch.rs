@@ -1,18 +1,30 @@
 use async_std::task;
-use dependency::Package;
+use futures::StreamExt;
+use dependency::resolver;
+use dependency::{cache_location, Package, PackageKind};
 use http::client::{Client, DownloadResult};
+use http::retry::Policy;
 use manifest::config::BuildK;
+use manifest::lock::Lockfile;
 use manifest::Manifest;
-use util::buildk_output::BuildkOutput;
+use serde_json::{json, Value};
+use util::buildk_output::{BuildkOutput, Failure};
 use util::colorize::{Color, Colors};
-use util::PartialConclusion;
+use util::hasher::Checksum;
+use util::message;
+use util::terminal::Terminal;
+use util::{paths, PartialConclusion};
 
-use crate::{deps, Command};
+use crate::{failed, Command};
 
 const DEBUG: bool = false;
+pub(crate) const DEFAULT_JOBS: usize = 8;
 
 pub(crate) struct Fetch<'a> {
     buildk: &'a BuildK,
+    update: Option<String>,
+    verify: bool,
+    jobs: Option<usize>,
 }
 
 impl<'a> Command for Fetch<'a> {
@@ -22,6 +34,7 @@ impl<'a> Command for Fetch<'a> {
         let mut output = BuildkOutput::new("fetch");
 
         match arg {
+            _ if self.verify => self.verify_cache(&mut output),
             Some(artifact) => self.fetch_from_arg(&mut output, artifact),
             None => self.fetch_from_manifest(&mut output),
         }
@@ -32,26 +45,166 @@ impl<'a> Command for Fetch<'a> {
 
 impl<'a> Fetch<'a> {
     fn fetch_from_manifest(&mut self, output: &mut BuildkOutput) {
-        let manifest = <Option<Manifest> as Clone>::clone(&self.buildk.manifest)
-            .expect("no buildk.toml found.");
+        let Some(manifest) = self.buildk.manifest.clone() else {
+            output.apply(failed("fetch", Failure::Config, "no buildk.toml found."));
+            return;
+        };
 
-        let deps = &manifest.all_packages.pkgs;
-        self.fetch_deps(deps, output)
+        let kinds = [PackageKind::Compile, PackageKind::Runtime, PackageKind::Test];
+
+        if let (None, Some(lock)) = (&self.update, manifest.up_to_date_lock()) {
+            let locked = lock.packages.iter().map(|locked| locked.package.clone()).collect::<Vec<_>>();
+            self.fetch_deps(|| locked.clone(), output);
+            if output.conclusion() != PartialConclusion::FAILED {
+                verify_lock(lock, output);
+            }
+            return;
+        }
+
+        let pins = self.pins(&manifest);
+        self.fetch_deps(|| manifest.resolve_unlocked(&kinds, &pins).selected, output);
+
+        if output.conclusion() != PartialConclusion::FAILED {
+            let resolution = manifest.resolve_unlocked(&kinds, &pins);
+            let lock = Lockfile::from_resolution(&resolution, &manifest.all_packages.pkgs, manifest.lock.as_ref());
+            if !verify_lock(&lock, output) {
+                return;
+            }
+            if let Err(err) = lock.write(&manifest.dir) {
+                eprintln!("{err}");
+                output.conclude(PartialConclusion::FAILED).stderr(err.to_string());
+            }
+        }
+    }
+
+    /// Locked versions to keep when resolving again.
+    /// `--update` without an artifact keeps nothing, with an artifact everything but that artifact.
+    /// A changed manifest keeps everything but the packages that were added or changed.
+    fn pins(&self, manifest: &Manifest) -> Vec<Package> {
+        let Some(lock) = &manifest.lock else {
+            return vec![];
+        };
+
+        lock.packages
+            .iter()
+            .map(|locked| &locked.package)
+            .filter(|pkg| match self.update.as_deref() {
+                None => !manifest.all_packages.pkgs.iter().any(|declared| {
+                    same_module(declared, pkg) && !lock.declared.contains(declared)
+                }),
+                Some("") => false,
+                Some(artifact) => !is_artifact(pkg, artifact),
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+impl<'a> Fetch<'a> {
+    /// Check the downloaded artifacts against the checksums recorded when they were verified.
+    /// Corrupted artifacts are deleted, so the next fetch downloads them again.
+    fn verify_cache(&mut self, output: &mut BuildkOutput) {
+        let files = match paths::all_files_recursive(vec![], cache_location()) {
+            Ok(files) => files,
+            Err(err) => {
+                output.conclude(PartialConclusion::FAILED).stderr(err.to_string());
+                return;
+            }
+        };
+
+        let mut verified = 0;
+        let mut unverified = 0;
+        for file in files.iter() {
+            // no checksum was published, only its sha256 when it was downloaded is known
+            let recorded = Checksum::ALL
+                .into_iter()
+                .find_map(|checksum| paths::read(&checksum.sidecar(file)).ok().map(|it| (checksum, checksum.sidecar(file), it)))
+                .or_else(|| {
+                    let sidecar = Checksum::unverified(file);
+                    paths::read(&sidecar).ok().map(|it| (Checksum::Sha256, sidecar, it))
+                });
+            let Some((checksum, sidecar, recorded)) = recorded else {
+                continue;
+            };
+
+            match checksum.matches(file, &recorded) {
+                Ok(true) if sidecar == Checksum::unverified(file) => unverified += 1,
+                Ok(true) => verified += 1,
+                Ok(false) => {
+                    let mismatch = format!("{} checksum mismatch for {}", checksum.extension(), file.display());
+                    let err = match std::fs::remove_file(file).and_then(|_| std::fs::remove_file(&sidecar)) {
+                        Ok(()) => format!("{mismatch}, removed"),
+                        Err(err) => format!("{mismatch}, and failed to remove it: {err}"),
+                    };
+                    eprintln!("{err}");
+                    output.append_stderr(err);
+                }
+                // the file is kept, it could not be read to compare
+                Err(err) => {
+                    let err = format!("failed to verify {}: {err}", file.display());
+                    eprintln!("{err}");
+                    output.append_stderr(err);
+                }
+            }
+        }
+
+        match unverified {
+            0 => output.stdout(format!("verified {verified} artifacts")),
+            _ => output.stdout(format!("verified {verified} artifacts, {unverified} without a published checksum")),
+        };
+
+        match output.get_stderr() {
+            Some(_) => output.conclude(PartialConclusion::FAILED),
+            None => output.conclude(PartialConclusion::SUCCESS),
+        };
+    }
+}
+
+/// The downloaded and cached jars must be the locked ones, false when one isn't.
+fn verify_lock(lock: &Lockfile, output: &mut BuildkOutput) -> bool {
+    match lock.verify() {
+        Ok(()) => true,
+        Err(err) => {
+            let err = format!("{err:#}\ndelete them from the cache to download them again");
+            eprintln!("\n{err}");
+            output.fail(Failure::Config).stderr(err);
+            false
+        }
+    }
+}
+
+fn same_module(a: &Package, b: &Package) -> bool {
+    a.namespace == b.namespace && a.name == b.name
+}
+
+/// `<namespace>..<name>` or just `<name>`
+fn is_artifact(pkg: &Package, artifact: &str) -> bool {
+    match artifact.split_once("..") {
+        Some((namespace, name)) => pkg.namespace.as_deref() == Some(namespace) && pkg.name == name,
+        None => pkg.name == artifact,
     }
 }
 
 impl<'a> Fetch<'a> {
     fn fetch_from_arg(&mut self, output: &mut BuildkOutput, artifact: String) {
-        let (name, namespace, version) = name_namespace_version(artifact);
-        let pkg = Package::new(name, namespace, version, dependency::PackageKind::Compile);
+        let (name, namespace, version) = match name_namespace_version(artifact) {
+            Ok(artifact) => artifact,
+            Err(err) => {
+                eprintln!("{err}");
+                output.fail(Failure::Config).stderr(err.to_string());
+                return;
+            }
+        };
+        let maven_local = self.buildk.manifest.as_ref().and_then(|it| it.maven_local());
+        let pkg = Package::new(name, namespace, version, dependency::PackageKind::Compile).maven_local(maven_local);
         self.fetch_dep(pkg, output)
     }
 }
 
-fn name_namespace_version(input: String) -> (String, Option<String>, String) {
+fn name_namespace_version(input: String) -> anyhow::Result<(String, Option<String>, String)> {
     let artifact = input.split("=").collect::<Vec<&str>>();
     if artifact.len() != 2 {
-        panic!(
+        anyhow::bail!(
             "unexpected artifact name. Missing artifact or version: <namespace>..<name>=<version>"
         );
     }
@@ -69,31 +222,49 @@ fn name_namespace_version(input: String) -> (String, Option<String>, String) {
             let namespace = artifact[0].to_string();
             (name, Some(namespace))
         }
-        _ => panic!("unexpected artifact name"),
+        _ => anyhow::bail!("unexpected artifact name"),
     };
 
-    (name, namespace, version)
+    Ok((name, namespace, version))
 }
 
 impl<'a> Fetch<'a> {
-    pub fn new(buildk: &'a BuildK) -> Fetch<'a> {
-        Fetch { buildk }
+    pub fn new(buildk: &'a BuildK, update: Option<String>, verify: bool, jobs: Option<usize>) -> Fetch<'a> {
+        Fetch { buildk, update, verify, jobs }
     }
 
-    fn fetch_dep(&mut self, pkg: Package, output: &mut BuildkOutput) {
-        self.fetch_deps(&[pkg], output)
+    /// `--jobs` wins over `[fetch] parallelism` in the manifest.
+    fn jobs(&self) -> usize {
+        self.jobs
+            .or(self.buildk.manifest.as_ref().and_then(|it| it.fetch.parallelism))
+            .unwrap_or(DEFAULT_JOBS)
+            .max(1)
     }
 
-    fn fetch_deps(&mut self, deps: &[Package], output: &mut BuildkOutput) {
-        let client = Client;
-
-        let downloads = task::block_on(async {
-            let all_deps = deps::find_dependent_deps(deps.to_vec(), vec![], 0, false).await;
+    fn fetch_dep(&mut self, pkg: Package, output: &mut BuildkOutput) {
+        let strategy = self.buildk.manifest.as_ref().map(|it| it.strategy).unwrap_or_default();
+        let roots = [pkg];
+        self.fetch_deps(|| resolver::resolve(&roots, strategy).selected, output)
+    }
 
-            println!("\rtotal deps: {}", all_deps.len());
+    /// Download what `resolve` selects that is not cached yet, at most `jobs` at a time.
+    fn fetch_deps<F>(&mut self, resolve: F, output: &mut BuildkOutput)
+    where
+        F: Fn() -> Vec<Package>,
+    {
+        let retry = self.buildk.manifest.as_ref().map(|it| Policy::from(&it.fetch)).unwrap_or_default();
+        let client = Client::new(retry);
+        let jobs = self.jobs();
+        let mut terminal = Terminal::default();
+        let mut total = 0;
+        let mut attempted: Vec<Package> = vec![];
+        let mut downloads = vec![];
 
-            all_deps
+        // transitives are only known once their descriptors are downloaded, resolve again until nothing is missing
+        loop {
+            let missing = resolve()
                 .into_iter()
+                .filter(|dep| !attempted.contains(dep))
                 .filter(|dep| {
                     if DEBUG {
                         match dep.is_cached() {
@@ -103,16 +274,39 @@ impl<'a> Fetch<'a> {
                     }
                     !dep.is_cached()
                 })
-                .map(|dep| {
-                    let config = self.buildk.clone();
-                    let client = client.clone();
+                .collect::<Vec<_>>();
 
-                    task::block_on(async {
-                        client.download_async(&dep, &config).await
-                    })
-                })
-                .collect::<Vec<_>>()
-        });
+            if missing.is_empty() {
+                break;
+            }
+
+            if self.buildk.offline {
+                let err = crate::offline_missing(&missing);
+                eprintln!("\n{}", &err);
+                output.append_stderr(err);
+                break;
+            }
+
+            total += missing.len();
+            terminal.update_spin(0, &progress(attempted.len(), total, client.bytes()));
+
+            let (client, buildk) = (&client, self.buildk);
+            task::block_on(async {
+                let mut pending = futures::stream::iter(missing.iter())
+                    .map(|dep| async move { (dep, client.download_async(dep, buildk).await) })
+                    .buffer_unordered(jobs);
+
+                while let Some((dep, download)) = pending.next().await {
+                    message::emit(artifact_message(dep, &download));
+                    downloads.push(download);
+                    terminal.update_spin(0, &progress(downloads.len(), total, client.bytes()));
+                }
+            });
+
+            attempted.extend(missing);
+        }
+
+        terminal.stop_spin(0);
 
         downloads
             .iter()
@@ -126,7 +320,7 @@ impl<'a> Fetch<'a> {
             });
 
         if output.get_stderr().is_some() {
-            output.conclude(PartialConclusion::FAILED);
+            output.fail(Failure::Network);
         } else if downloads.iter().any(|d| d.is_downloaded()) {
             output.conclude(PartialConclusion::SUCCESS);
         } else {
@@ -135,6 +329,28 @@ impl<'a> Fetch<'a> {
     }
 }
 
+/// The `artifact` message of `--message-format json`.
+fn artifact_message(pkg: &Package, download: &DownloadResult) -> Value {
+    let (outcome, error) = match download {
+        DownloadResult::Downloaded => ("downloaded", None),
+        DownloadResult::Exist => ("cached", None),
+        DownloadResult::Failed(err) => ("failed", Some(err)),
+    };
+
+    json!({
+        "reason": "artifact",
+        "coordinates": pkg.coordinates(),
+        "scope": Into::<String>::into(pkg.kind.clone()).to_lowercase(),
+        "outcome": outcome,
+        "file": pkg.jar_absolute_path(),
+        "error": error,
+    })
+}
+
+fn progress(done: usize, total: usize, bytes: u64) -> String {
+    format!("fetched {done}/{total} artifacts, {:.1} MB ", bytes as f64 / 1_000_000.0)
+}
+
 fn print_status(pkg: &Package, status: &str, color: Color, depth: usize) {
     let display = format!(
         "{:>depth$}{:<14}{}:{}",
@@ -3,7 +3,9 @@ use std::{
     io::Write,
 };
 
-use util::buildk_output::BuildkOutput;
+use anyhow::Context;
+use util::buildk_output::{BuildkOutput, Failure};
+use util::PartialConclusion;
 
 use crate::Command;
 
@@ -15,29 +17,42 @@ impl<'a> Command for Init {
     fn execute(&mut self, _arg: Option<Self::Item>) -> BuildkOutput {
         let mut output = BuildkOutput::new("init");
 
-        let cwd = std::env::current_dir().expect("path to current working directory");
+        match self.init() {
+            Ok(()) => output.conclude(PartialConclusion::SUCCESS),
+            Err(err) => {
+                eprintln!("{err:#}");
+                output.fail(Failure::Config).stderr(format!("{err:#}"))
+            }
+        };
+
+        output
+    }
+}
+
+impl Init {
+    pub fn new() -> Init {
+        Init {}
+    }
+
+    fn init(&self) -> anyhow::Result<()> {
+        let cwd = std::env::current_dir().context("path to current working directory")?;
 
         let buildk = cwd.join("buildk.toml");
-        File::create(buildk).expect("Unable to create buildk.tom");
+        anyhow::ensure!(!buildk.exists(), "buildk.toml already exists");
+        File::create(buildk).context("Unable to create buildk.toml")?;
 
-        create_dir(cwd.join("src")).expect("Unable to create src directory");
+        create_dir(cwd.join("src")).context("Unable to create src directory")?;
         let main = cwd.join("src").join("Main.kt");
-        let mut main = File::create(main).expect("Unable to create Main.kt");
+        let mut main = File::create(main).context("Unable to create Main.kt")?;
         main.write_all(b"fun main() {\n    println(\"Hello, World!\")\n}")
-            .expect("Unable to write to Main.kt");
+            .context("Unable to write to Main.kt")?;
 
-        create_dir(cwd.join("test")).expect("Unable to create test directory");
+        create_dir(cwd.join("test")).context("Unable to create test directory")?;
         let test = cwd.join("test").join("MainTest.kt");
-        let mut test = File::create(test).expect("Unable to create MainTest.kt");
+        let mut test = File::create(test).context("Unable to create MainTest.kt")?;
         test.write_all(b"import org.junit.jupiter.api.Test\nimport kotlin.test.assertEquals\n\nclass MainTest {\n    @Test\n    fun test() {\n        assertEquals(1, 1)\n    }\n}")
-            .expect("Unable to write to MainTest.kt");
-
-        output.conclude(util::PartialConclusion::SUCCESS).to_owned()
-    }
-}
+            .context("Unable to write to MainTest.kt")?;
 
-impl Init {
-    pub fn new() -> Init {
-        Init {}
+        Ok(())
     }
 }
@@ -1,31 +1,50 @@
-use clap::{command, Parser, Subcommand, ValueEnum};
+use std::fmt::Display;
 
+use clap::{command, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+
+use add::Add;
 use build::Build;
 use clean::Clean;
 use config::Config;
+use daemon::Daemon;
 use dep_path::DepPath;
+use dependency::version::Upgrade;
+use dependency::{Package, PackageKind};
 use deps::Deps;
 use fetch::Fetch;
 use init::Init;
 use manifest::config::BuildK;
+use outdated::Outdated;
 use process::{java::Java, kotlin::Kotlin, Process};
 use release::Release;
+use remove::Remove;
 use run::Run;
+use serde_json::json;
 use test::Test;
 use tree::Tree;
-use util::buildk_output::BuildkOutput;
+use update::Update;
+use why::Why;
+use util::buildk_output::{BuildkOutput, Failure};
+use util::message;
 
+mod add;
 mod build;
 mod clean;
 mod config;
+mod daemon;
 mod dep_path;
 mod deps;
+mod export;
 mod fetch;
 mod init;
+mod outdated;
 mod release;
+mod remove;
 mod run;
 mod test;
 mod tree;
+mod update;
+mod why;
 
 #[derive(Parser)]
 #[command(name = "")]
@@ -37,20 +56,88 @@ pub struct Cli {
 
     #[arg(short = 'q')]
     quiet: bool,
+
+    /// Fail when buildk.lock is missing or does not match buildk.toml
+    #[arg(long, global = true)]
+    locked: bool,
+
+    /// Only use the dependencies in ~/.buildk/cache, never connect to a repo
+    #[arg(long, global = true, env = "BUILDK_OFFLINE", value_parser = clap::builder::FalseyValueParser::new())]
+    offline: bool,
+
+    /// Compile with kotlinc instead of the long-lived compile server
+    #[arg(long, global = true, env = "BUILDK_NO_DAEMON", value_parser = clap::builder::FalseyValueParser::new())]
+    no_daemon: bool,
+
+    /// Print newline-delimited JSON messages on stdout instead of text, the text goes to stderr
+    #[arg(long, global = true, value_name = "FORMAT", default_value_t = MessageFormat::Human, value_enum)]
+    message_format: MessageFormat,
+
+    /// The name of the subcommand, for the messages
+    #[arg(skip)]
+    name: String,
 }
 
 impl Cli {
     pub fn init() -> Cli {
-        Cli::parse()
+        let matches = Cli::command().get_matches();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+        cli.name = matches.subcommand_name().unwrap_or_default().to_owned();
+
+        if cli.message_format == MessageFormat::Json {
+            if let Err(err) = message::json() {
+                eprintln!("--message-format json: {err}");
+            }
+        }
+
+        cli
     }
 
     pub fn is_quiet(&self) -> bool {
         self.quiet
     }
+
+    pub fn apply(&mut self, buildk: &BuildK) -> BuildkOutput {
+        let buildk = &buildk.clone().offline(self.offline).daemon(!self.no_daemon);
+        message::emit(json!({ "reason": "command-started", "command": self.name }));
+
+        if let Err(err) = self.command.check_manifest(buildk) {
+            return failed("manifest", Failure::Config, err);
+        }
+
+        if self.offline {
+            if let Err(err) = self.command.check_offline(buildk) {
+                return failed("offline", Failure::Network, err);
+            }
+        }
+
+        if self.locked {
+            if let Err(err) = self.command.check_locked(buildk) {
+                return failed("locked", Failure::Config, err);
+            }
+        }
+
+        self.command.apply(buildk)
+    }
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
+    /// Add a dependency to buildk.toml and fetch it
+    Add {
+        /// <namespace>:<name>[:<version>], the latest release when the version is left out
+        #[arg(value_name = "ARTIFACT")]
+        artifact: String,
+
+        /// Add to [test]
+        #[arg(long, conflicts_with = "runtime")]
+        test: bool,
+
+        /// Add to [runtime]
+        #[arg(long)]
+        runtime: bool,
+    },
+
     /// Build the project
     #[command(short_flag = 'b')]
     Build {
@@ -62,6 +149,10 @@ pub enum Commands {
         value_enum
         )]
         set: Set,
+
+        /// Build only this workspace member (and the members it depends on)
+        #[arg(short = 'p', long = "package", value_name = "MODULE")]
+        module: Option<String>,
     },
 
     /// Clean the output directory
@@ -80,24 +171,60 @@ pub enum Commands {
     /// Show the project configuration
     Config,
 
+    /// Show or stop the compile servers keeping kotlinc warm between builds
+    Daemon {
+        #[arg(value_name = "ACTION", default_value_t = DaemonAction::Status, value_enum)]
+        action: DaemonAction,
+    },
+
     /// Print the dependencies
     Deps {
         #[arg(value_name = "LIMIT")]
         limit: Option<usize>,
+
+        /// Print the resolved graph as a tree, as JSON, as Graphviz DOT or as a CycloneDX SBOM
+        #[arg(long, value_name = "FORMAT", default_value_t = GraphFormat::Tree, value_enum)]
+        format: GraphFormat,
     },
 
     /// Fetch the dependencies
     Fetch {
         #[arg(value_name = "ARTIFACT")]
         artifact: Option<String>,
+
+        /// Resolve again and update buildk.lock, only for ARTIFACT (<namespace>..<name>) when given
+        #[arg(long, value_name = "ARTIFACT", num_args = 0..=1, default_missing_value = "")]
+        update: Option<String>,
+
+        /// Check the artifacts in ~/.buildk/cache against their verified checksums, offline
+        #[arg(long)]
+        verify: bool,
+
+        /// Download at most N artifacts at a time, overrides [fetch] parallelism
+        #[arg(short, long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: Option<u16>,
     },
 
     /// Initialize the project
     Init, 
 
+    /// Print the dependencies with newer releases
+    Outdated {
+        /// Include alpha, beta, rc and snapshot versions
+        #[arg(long)]
+        pre_release: bool,
+    },
+
     /// Create a release (jar)
     Release,
 
+    /// Remove a dependency from buildk.toml
+    Remove {
+        /// <namespace>:<name>, or <name> in any namespace
+        #[arg(value_name = "ARTIFACT")]
+        artifact: String,
+    },
+
     /// Run the project
     #[command(short_flag = 'r')]
     Run {
@@ -115,6 +242,24 @@ pub enum Commands {
     /// Print the build tree
     Tree,
 
+    /// Update the versions in buildk.toml and fetch them
+    Update {
+        /// How far versions may move, e.g. 1.2.3 -> 1.2.x (patch), 1.x (minor) or anything newer (major)
+        #[arg(value_name = "POLICY", default_value_t = UpdatePolicy::Minor, value_enum)]
+        policy: UpdatePolicy,
+
+        /// Include alpha, beta, rc and snapshot versions
+        #[arg(long)]
+        pre_release: bool,
+    },
+
+    /// Print every path from a declared dependency to an artifact
+    Why {
+        /// <namespace>:<name>[:<version>], or <name> in any namespace
+        #[arg(value_name = "ARTIFACT")]
+        artifact: String,
+    },
+
     Path {
         #[arg(value_name = "DEP")]
         dep: String,
@@ -128,6 +273,43 @@ pub enum Set {
     Test,
 }
 
+#[derive(ValueEnum, Copy, Clone, PartialEq, Eq)]
+pub enum DaemonAction {
+    Status,
+    Stop,
+}
+
+#[derive(ValueEnum, Copy, Clone, PartialEq, Eq)]
+pub enum MessageFormat {
+    Human,
+    Json,
+}
+
+#[derive(ValueEnum, Copy, Clone, PartialEq, Eq)]
+pub enum GraphFormat {
+    Tree,
+    Json,
+    Dot,
+    Cyclonedx,
+}
+
+#[derive(ValueEnum, Copy, Clone, PartialEq, Eq)]
+pub enum UpdatePolicy {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl From<UpdatePolicy> for Upgrade {
+    fn from(policy: UpdatePolicy) -> Self {
+        match policy {
+            UpdatePolicy::Patch => Upgrade::Patch,
+            UpdatePolicy::Minor => Upgrade::Minor,
+            UpdatePolicy::Major => Upgrade::Major,
+        }
+    }
+}
+
 #[derive(ValueEnum, Copy, Clone, PartialEq, Eq)]
 pub enum CleanSet {
     All,
@@ -136,45 +318,142 @@ pub enum CleanSet {
     Release,
 }
 
+fn offline_missing(missing: &[Package]) -> String {
+    let missing = missing
+        .iter()
+        .map(|pkg| format!("  {}", pkg.coordinates()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!("offline, missing from ~/.buildk/cache:\n{missing}")
+}
+
 trait Command {
     type Item;
 
     fn execute(&mut self, arg: Option<Self::Item>) -> BuildkOutput;
 }
 
+fn with_kotlin(buildk: &BuildK, command: impl FnOnce(&Kotlin) -> BuildkOutput) -> BuildkOutput {
+    match Kotlin::new(buildk) {
+        Ok(kotlin) => command(&kotlin),
+        Err(err) => failed("kotlin", Failure::Config, format!("Kotlin not found: {err:#}")),
+    }
+}
+
+fn with_java(buildk: &BuildK, command: impl FnOnce(&Java) -> BuildkOutput) -> BuildkOutput {
+    match Java::new(buildk) {
+        Ok(java) => command(&java),
+        Err(err) => failed("java", Failure::Config, format!("Java not found: {err:#}")),
+    }
+}
+
+/// Print `err` and conclude `command` as failed.
+pub(crate) fn failed(command: &str, failure: Failure, err: impl Display) -> BuildkOutput {
+    eprintln!("{err}");
+    BuildkOutput::new(command).fail(failure).stderr(err.to_string()).to_owned()
+}
+
 impl Commands {
-    pub fn apply(&mut self, buildk: &BuildK) -> BuildkOutput {
-        let kotlin = match Kotlin::new(buildk) {
-            Ok(kotlin) => kotlin,
-            Err(err) => panic!("{}: {}", err, "Kotlin not found.")
+    /// Every command but `init` needs a valid buildk.toml.
+    fn check_manifest(&self, buildk: &BuildK) -> anyhow::Result<()> {
+        if buildk.manifest.is_some() || matches!(self, Commands::Init | Commands::Daemon { .. } | Commands::Path { .. }) {
+            return Ok(());
+        }
+
+        match &buildk.manifest_error {
+            Some(err) => anyhow::bail!("buildk.toml is not valid: {err}"),
+            None => anyhow::bail!("no buildk.toml found, run `buildk init` to create one"),
+        }
+    }
+
+    /// With `--offline`, every dependency the command needs must be downloaded already.
+    /// `fetch` reports what is missing itself.
+    fn check_offline(&self, buildk: &BuildK) -> anyhow::Result<()> {
+        let kinds = match self {
+            Commands::Build { .. } | Commands::Test { .. } => {
+                vec![PackageKind::Compile, PackageKind::Runtime, PackageKind::Test]
+            }
+            Commands::Run { .. } | Commands::Release => vec![PackageKind::Compile, PackageKind::Runtime],
+            _ => return Ok(()),
         };
 
-        let java = match Java::new(buildk) {
-            Ok(java) => java,
-            Err(err) => panic!("{}: {}", err, "Java not found.")
+        let missing = match &buildk.manifest {
+            Some(manifest) => manifest.missing(&kinds),
+            None => return Ok(()),
+        };
+
+        match missing.is_empty() {
+            true => Ok(()),
+            false => anyhow::bail!(offline_missing(&missing)),
+        }
+    }
+
+    /// With `--locked`, dependencies must come from an up-to-date buildk.lock.
+    fn check_locked(&self, buildk: &BuildK) -> anyhow::Result<()> {
+        if matches!(self, Commands::Init | Commands::Clean { .. } | Commands::Daemon { .. }) {
+            return Ok(());
+        }
+
+        if let Commands::Fetch { update: Some(_), .. } = self {
+            anyhow::bail!("cannot update buildk.lock with --locked");
+        }
+
+        if matches!(self, Commands::Add { .. } | Commands::Remove { .. } | Commands::Update { .. }) {
+            anyhow::bail!("cannot change buildk.toml with --locked");
+        }
+
+        let Some(manifest) = &buildk.manifest else {
+            return Ok(());
         };
 
-        let tree = Tree::new(buildk);
+        match &manifest.lock {
+            Some(lock) => {
+                lock.check(&manifest.all_packages.pkgs)?;
+                lock.verify()
+            }
+            None => anyhow::bail!("buildk.lock not found, run `buildk fetch` to create it"),
+        }
+    }
 
+    pub fn apply(&mut self, buildk: &BuildK) -> BuildkOutput {
         match self {
-            Commands::Build { set } => {
-                match tree {
-                    Ok(tree) => Build::new(buildk, &kotlin, &tree).execute(Some(*set)),
-                    Err(e) => panic!("{}", e),
+            Commands::Add { artifact, test, runtime } => {
+                let kind = match (test, runtime) {
+                    (true, _) => PackageKind::Test,
+                    (_, true) => PackageKind::Runtime,
+                    _ => PackageKind::Compile,
+                };
+                Add::new(buildk, kind).execute(Some(artifact.clone()))
+            }
+            Commands::Build { set, module } => {
+                let workspace = buildk.manifest.as_ref().and_then(|it| it.workspace.as_ref());
+                match (workspace, module) {
+                    (Some(workspace), module) => build::build_workspace(buildk, workspace, module.as_deref(), *set),
+                    (None, Some(module)) => failed("build", Failure::Config, format!("cannot build '{module}', buildk.toml has no [workspace]")),
+                    (None, None) => match Tree::new(buildk) {
+                        Ok(tree) => with_kotlin(buildk, |kotlin| with_java(buildk, |java| Build::new(buildk, kotlin, java, &tree).execute(Some(*set)))),
+                        Err(err) => failed("build", Failure::Config, format!("{err:#}")),
+                    },
                 }
             }, 
             Commands::Clean { set } => Clean::new(buildk).execute(Some(*set)),
-            Commands::Config => Config::new(buildk, &kotlin, &java).execute(None),
-            Commands::Deps { limit } => Deps::new(buildk).execute(*limit),
-            Commands::Fetch { artifact } => Fetch::new(buildk).execute(artifact.clone()),
+            Commands::Daemon { action } => Daemon::new(buildk).execute(Some(*action)),
+            Commands::Config => with_kotlin(buildk, |kotlin| with_java(buildk, |java| Config::new(buildk, kotlin, java).execute(None))),
+            Commands::Deps { limit, format } => Deps::new(buildk, *format).execute(*limit),
+            Commands::Fetch { artifact, update, verify, jobs } => Fetch::new(buildk, update.clone(), *verify, jobs.map(usize::from)).execute(artifact.clone()),
             Commands::Init => Init::new().execute(None),
-            Commands::Release => Release::new(buildk, &kotlin).execute(None),
-            Commands::Run { name } => Run::new(buildk, &kotlin).execute(name.clone()),
-            Commands::Test { name } =>Test::new(buildk, &java).execute(name.clone()), 
-            Commands::Tree => match tree {
+            Commands::Outdated { pre_release } => Outdated::new(buildk, *pre_release).execute(None),
+            Commands::Release => with_kotlin(buildk, |kotlin| Release::new(buildk, kotlin).execute(None)),
+            Commands::Remove { artifact } => Remove::new(buildk).execute(Some(artifact.clone())),
+            Commands::Run { name } => with_kotlin(buildk, |kotlin| Run::new(buildk, kotlin).execute(name.clone())),
+            Commands::Test { name } => with_java(buildk, |java| Test::new(buildk, java).execute(name.clone())),
+            Commands::Tree => match Tree::new(buildk) {
                 Ok(mut tree) => tree.execute(None),
-                Err(e) => panic!("{}", e),
+                Err(err) => failed("tree", Failure::Config, format!("{err:#}")),
             },
+            Commands::Update { policy, pre_release } => Update::new(buildk, *pre_release).execute(Some((*policy).into())),
+            Commands::Why { artifact } => Why::new(buildk).execute(Some(artifact.to_owned())),
             Commands::Path { dep } => DepPath::new(buildk).execute(Some(dep.to_owned())),
         }
     }
@@ -0,0 +1,126 @@
+use anyhow::Context;
+use async_std::task;
+use dependency::version::{self, Upgrade};
+use dependency::Package;
+use futures::StreamExt;
+use http::client::{Client, DownloadResult};
+use http::retry::Policy;
+use manifest::config::BuildK;
+use manifest::edit::ManifestEditor;
+use util::buildk_output::{BuildkOutput, Failure};
+use util::colorize::Colorize;
+use util::PartialConclusion;
+
+use crate::fetch::DEFAULT_JOBS;
+use crate::Command;
+
+pub(crate) struct Outdated<'a> {
+    buildk: &'a BuildK,
+    pre_releases: bool,
+}
+
+impl<'a> Command for Outdated<'a> {
+    type Item = ();
+
+    fn execute(&mut self, _arg: Option<Self::Item>) -> BuildkOutput {
+        let mut output = BuildkOutput::new("outdated");
+
+        let declared = match self.buildk.manifest.as_ref().context("no buildk.toml found.").and_then(|it| ManifestEditor::open(&it.dir)) {
+            Ok(editor) => editor.declared(),
+            Err(err) => {
+                eprintln!("{err:#}");
+                return output.conclude(PartialConclusion::FAILED).stderr(format!("{err:#}")).to_owned();
+            }
+        };
+
+        let (versions, failures) = available_versions(self.buildk, &declared);
+        let mut rows = vec![];
+        for (pkg, available) in versions {
+            let upgrades = Upgrade::ALL.map(|upgrade| upgrade.select(&pkg.version, &available, self.pre_releases));
+            if upgrades.iter().any(Option::is_some) {
+                rows.push((pkg, upgrades));
+            }
+        }
+
+        match rows.is_empty() {
+            true => output.stdout("all dependencies are up to date".into()),
+            false => output.stdout(table(&rows)),
+        };
+
+        if !failures.is_empty() {
+            return output.fail(Failure::Network).stderr(failures.join("\n")).to_owned();
+        }
+
+        output.conclude(PartialConclusion::SUCCESS).to_owned()
+    }
+}
+
+impl<'a> Outdated<'a> {
+    pub fn new(buildk: &'a BuildK, pre_releases: bool) -> Outdated<'a> {
+        Outdated { buildk, pre_releases }
+    }
+}
+
+fn table(rows: &[(Package, [Option<String>; 3])]) -> String {
+    let width = rows.iter().map(|(pkg, _)| artifact(pkg).len()).max().unwrap_or_default() + 2;
+    let header = format!("{:<width$}{:<16}{:<16}{:<16}{}", "artifact", "current", "patch", "minor", "major");
+
+    let lines = rows.iter().map(|(pkg, upgrades)| {
+        let [patch, minor, major] = upgrades.clone().map(|it| it.unwrap_or_else(|| "-".into()));
+        format!("{:<width$}{:<16}{:<16}{:<16}{}", artifact(pkg), pkg.version, patch, minor, major)
+    });
+
+    std::iter::once(header.as_gray()).chain(lines).collect::<Vec<_>>().join("\n")
+}
+
+fn artifact(pkg: &Package) -> String {
+    format!("{}:{}", pkg.namespace.as_deref().unwrap_or_default(), pkg.name)
+}
+
+/// The versions in the maven-metadata.xml of the repos for each package with an exact version.
+/// The metadata is downloaded when it is older than a day, offline the cached metadata is used.
+/// Also returns why the lookup failed for each package whose metadata could not be downloaded or had no versions.
+pub(crate) fn available_versions(buildk: &BuildK, pkgs: &[Package]) -> (Vec<(Package, Vec<String>)>, Vec<String>) {
+    let manifest = buildk.manifest.as_ref();
+    let client = Client::new(manifest.map(|it| Policy::from(&it.fetch)).unwrap_or_default());
+    let jobs = manifest.and_then(|it| it.fetch.parallelism).unwrap_or(DEFAULT_JOBS);
+
+    let pkgs = pkgs
+        .iter()
+        .filter(|pkg| pkg.namespace.is_some() && !pkg.version.is_empty() && !pkg.is_dynamic())
+        .collect::<Vec<_>>();
+
+    let looked_up: Vec<_> = task::block_on(async {
+        futures::stream::iter(pkgs)
+            .map(|pkg| {
+                // any version, so the package points at the module directory with the metadata
+                let module = Package::new(pkg.name.clone(), pkg.namespace.clone(), "+".into(), pkg.kind.clone())
+                    .maven_local(pkg.maven_local.clone());
+                let client = &client;
+                async move {
+                    let downloaded = client.download_async(&module, buildk).await;
+                    let available = version::available(&module.location, module.maven_local.as_deref());
+                    let failed = match downloaded {
+                        _ if available.is_empty() => Some(format!("no versions of {} found", pkg.coordinates())),
+                        DownloadResult::Failed(err) if !buildk.offline => Some(err),
+                        _ => None,
+                    };
+                    (pkg.clone(), available, failed)
+                }
+            })
+            .buffered(jobs)
+            .collect()
+            .await
+    });
+
+    let mut versions = vec![];
+    let mut failures = vec![];
+    for (pkg, available, failed) in looked_up {
+        if let Some(err) = failed {
+            eprintln!("{err}");
+            failures.push(err);
+        }
+        versions.push((pkg, available));
+    }
+    (versions, failures)
+}
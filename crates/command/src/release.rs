@@ -1,8 +1,9 @@
-use manifest::{config::BuildK, Manifest};
+use manifest::config::BuildK;
 use process::kotlin::Kotlin;
-use util::buildk_output::BuildkOutput;
+use util::buildk_output::{BuildkOutput, Failure};
+use util::PartialConclusion;
 
-use crate::Command;
+use crate::{failed, Command};
 
 pub (crate) struct Release<'a> {
     buildk: &'a BuildK,
@@ -14,16 +15,21 @@ impl <'a> Command for Release<'a> {
 
     fn execute(&mut self, _arg: Option<Self::Item>) -> BuildkOutput {
         let mut output = BuildkOutput::new("release");
-        // FIXME
-        let manifest = <Option<Manifest> as Clone>::clone(&self.buildk.manifest)
-            .expect("no buildk.toml found.");
+        let Some(manifest) = self.buildk.manifest.clone() else {
+            return failed("release", Failure::Config, "no buildk.toml found.");
+        };
 
-        self.kotlin.builder()
+        let mut output = self.kotlin.builder()
             .source(&manifest.project.src)
             .include_runtime()
             .workdir(&manifest.project.path)
             .target(&manifest.project.out_paths().release)
-            .compile(&mut output)
+            .compile(&mut output);
+
+        if output.conclusion() == PartialConclusion::FAILED {
+            output.fail(Failure::Compile);
+        }
+        output
     }
 }
 
@@ -0,0 +1,72 @@
+use anyhow::Context;
+use manifest::config::BuildK;
+use manifest::edit::ManifestEditor;
+use manifest::Manifest;
+use util::buildk_output::{BuildkOutput, Failure};
+use util::PartialConclusion;
+
+use crate::fetch::Fetch;
+use crate::{failed, Command};
+
+pub(crate) struct Remove<'a> {
+    buildk: &'a BuildK,
+}
+
+impl<'a> Command for Remove<'a> {
+    type Item = String;
+
+    fn execute(&mut self, arg: Option<Self::Item>) -> BuildkOutput {
+        let mut output = BuildkOutput::new("remove");
+        let Some(artifact) = arg else {
+            return failed("remove", Failure::Config, "an artifact is required");
+        };
+
+        if let Err(err) = self.remove(&artifact, &mut output) {
+            eprintln!("{err:#}");
+            output.conclude(PartialConclusion::FAILED).stderr(format!("{err:#}"));
+        }
+
+        output
+    }
+}
+
+impl<'a> Remove<'a> {
+    pub fn new(buildk: &'a BuildK) -> Remove<'a> {
+        Remove { buildk }
+    }
+
+    /// Remove `<namespace>:<name>` or `<name>` from every dependency table of buildk.toml.
+    fn remove(&mut self, artifact: &str, output: &mut BuildkOutput) -> anyhow::Result<()> {
+        let manifest = self.buildk.manifest.as_ref().context("no buildk.toml found.")?;
+
+        let (namespace, name) = match artifact.split_once(':') {
+            Some((namespace, name)) => (Some(namespace), name),
+            None => (None, artifact),
+        };
+
+        let mut editor = ManifestEditor::open(&manifest.dir)?;
+        let removed = editor.remove(namespace, name);
+        anyhow::ensure!(!removed.is_empty(), "{artifact} is not a dependency in buildk.toml");
+        editor.save()?;
+
+        let sections = removed
+            .into_iter()
+            .map(|kind| format!("[{}]", Into::<String>::into(kind).to_lowercase()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        output.stdout(format!("removed {artifact} from {sections}"));
+
+        // keep buildk.lock in line with the manifest
+        if manifest.lock.is_some() {
+            let buildk = BuildK {
+                manifest: Some(Manifest::try_from_dir(&manifest.dir)?),
+                ..self.buildk.clone()
+            };
+            output.apply(Fetch::new(&buildk, None, false, None).execute(None));
+        } else {
+            output.conclude(PartialConclusion::SUCCESS);
+        }
+
+        Ok(())
+    }
+}
@@ -1,8 +1,9 @@
-use manifest::{config::BuildK, Manifest};
+use dependency::PackageKind;
+use manifest::config::BuildK;
 use process::kotlin::Kotlin;
-use util::buildk_output::BuildkOutput;
+use util::buildk_output::{BuildkOutput, Failure};
 
-use crate::Command;
+use crate::{failed, Command};
 
 pub(crate) struct Run<'a> {
     buildk: &'a BuildK,
@@ -15,20 +16,16 @@ impl<'a> Command for Run<'a> {
     fn execute(&mut self, arg: Option<Self::Item>) -> BuildkOutput {
         let mut output = BuildkOutput::new("run");
 
-        // FIXME
-        let manifest = <Option<Manifest> as Clone>::clone(&self.buildk.manifest)
-            .expect("no buildk.toml found.");
+        let Some(manifest) = self.buildk.manifest.clone() else {
+            return failed("run", Failure::Config, "no buildk.toml found.");
+        };
 
-        let runtime_deps = manifest.runtime_deps;
-        let runtime_paths = runtime_deps.pkgs.iter().map(|dep| dep.jar_absolute_path()).collect::<Vec<_>>();
-        let platform_deps = manifest.compile_deps;
-        let platform_paths = platform_deps.pkgs.iter().map(|dep| dep.jar_absolute_path()).collect::<Vec<_>>();
+        let runtime_paths = manifest.classpath(&[PackageKind::Compile, PackageKind::Runtime]);
 
         let out_paths = &manifest.project.out_paths();
         let mut classpath = vec![&out_paths.src, &manifest.project.src];
 
         classpath.extend(runtime_paths.iter());
-        classpath.extend(platform_paths.iter());
 
         let main = match arg {
             Some(class) => class.to_string() + "Kt",
@@ -1,12 +1,15 @@
 // use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use dependency::PackageKind;
 use manifest::config::BuildK;
-use manifest::Manifest;
 use process::java::{Java, JavaBuilder};
-use util::buildk_output::BuildkOutput;
+use serde_json::{json, Value};
+use util::buildk_output::{BuildkOutput, Failure};
+use util::{message, PartialConclusion};
 
 // use crate::tree::HeaderKt;
-use crate::Command;
+use crate::{failed, Command};
 
 pub(crate) struct Test<'a> {
     buildk: &'a BuildK,
@@ -19,17 +22,11 @@ impl<'a> Command for Test<'a> {
     fn execute(&mut self, _arg: Option<Self::Item>) -> BuildkOutput {
         let mut output = BuildkOutput::new("test");
 
-        // FIXME
-        let manifest = <Option<Manifest> as Clone>::clone(&self.buildk.manifest)
-            .expect("no buildk.toml found.");
+        let Some(manifest) = self.buildk.manifest.clone() else {
+            return failed("test", Failure::Config, "no buildk.toml found.");
+        };
 
-        let test_deps = manifest
-            .test_deps
-            .pkgs
-            .iter()
-            // .filter(|pkg| pkg.name != "junit-platform-console-standalone")
-            .map(|pkg| pkg.jar_absolute_path())
-            .collect::<Vec<_>>();
+        let test_deps = manifest.classpath(&[PackageKind::Compile, PackageKind::Runtime, PackageKind::Test]);
 
         // let junit = manifest
         //     .test_deps
@@ -39,13 +36,6 @@ impl<'a> Command for Test<'a> {
         //     .map(|pkg| pkg.jar_absolute_path())
         //     .expect("missing junit");
 
-        let kotlin_stdlib = manifest.compile_deps
-            .pkgs
-            .iter()
-            .find(|pkg| pkg.name == "kotlin-stdlib")
-            .map(|pkg| pkg.jar_absolute_path())
-            .expect("kotlin-stdlib");
-
         // let kotlin_stdlib = manifest
         //     .kotlin_home
         //     .unwrap()
@@ -54,7 +44,7 @@ impl<'a> Command for Test<'a> {
         //     .join("kotlin-stdlib.jar");
 
         let out_paths = &manifest.project.out_paths();
-        let mut classpath = vec![&out_paths.src, &out_paths.test, &kotlin_stdlib];
+        let mut classpath = vec![&out_paths.src, &out_paths.test];
         // let mut classpath = vec![&out_paths.src, &out_paths.test, &junit, &kotlin_stdlib];
 
         // TODO: working example: java -jar /Users/robin/.buildk/cache/org.junit.platform/junit-platform-console-standalone/1.10.2/pkg.jar -cp out/test:out/src:/Users/robin/.buildk/cache/org.jetbrains.kotlin/kotlin-stdlib/1.9.22/pkg.jar --scan-classpath --disable-banner --exclude-engine=junit-vintage --exclude-engine=junit-platform-suite
@@ -65,13 +55,68 @@ impl<'a> Command for Test<'a> {
         let mut java = self.java.builder();
         java.workdir(&manifest.project.path)
             .classpath(classpath);
-        let java = self.junit5(&mut java);
+        let java = self.junit5(&mut java, &out_paths.test_report);
         // let java = self.testng(&mut java);
 
-        java.run(&mut output)
+        let mut output = java.run(&mut output);
+        if message::is_json() {
+            test_messages(&out_paths.test_report).into_iter().for_each(message::emit);
+        }
+        if output.conclusion() == PartialConclusion::FAILED {
+            output.fail(Failure::Test);
+        }
+        output
     }
 }
 
+/// The `test` messages of `--message-format json`, one per testcase in the JUnit XML reports.
+fn test_messages(report_dir: &Path) -> Vec<Value> {
+    let Ok(entries) = std::fs::read_dir(report_dir) else {
+        return vec![];
+    };
+
+    let mut reports = entries
+        .filter_map(|entry| entry.ok().map(|it| it.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "xml"))
+        .collect::<Vec<_>>();
+    reports.sort();
+
+    reports
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|content| testcases(&content))
+        .collect()
+}
+
+fn testcases(report: &str) -> Vec<Value> {
+    let Ok(doc) = roxmltree::Document::parse(report) else {
+        return vec![];
+    };
+
+    doc.descendants()
+        .filter(|node| node.has_tag_name("testcase"))
+        .map(|testcase| {
+            let problem = testcase
+                .children()
+                .find(|it| it.has_tag_name("failure") || it.has_tag_name("error") || it.has_tag_name("skipped"));
+            let outcome = match problem.map(|it| it.tag_name().name()) {
+                None => "passed",
+                Some("skipped") => "skipped",
+                Some(_) => "failed",
+            };
+
+            json!({
+                "reason": "test",
+                "class": testcase.attribute("classname"),
+                "name": testcase.attribute("name"),
+                "outcome": outcome,
+                "duration_ms": testcase.attribute("time").and_then(|it| it.parse::<f64>().ok()).map(|secs| secs * 1000.0),
+                "message": problem.and_then(|it| it.attribute("message")),
+            })
+        })
+        .collect()
+}
+
 impl<'a> Test<'_> {
     pub fn new(buildk: &'a BuildK, java: &'a Java) -> Test<'a> {
         Test { buildk, java }
@@ -91,13 +136,10 @@ impl<'a> Test<'_> {
     }
 
     #[allow(dead_code)]
-    fn junit5(&'a self, java: &'a mut JavaBuilder<'a>) -> &'a mut JavaBuilder {
-        let manifest = <Option<Manifest> as Clone>::clone(&self.buildk.manifest)
-            .expect("no buildk.toml found.");
-
+    fn junit5(&'a self, java: &'a mut JavaBuilder<'a>, test_report: &PathBuf) -> &'a mut JavaBuilder {
         java
             .args(&["org.junit.platform.console.ConsoleLauncher", "--scan-classpath"])
-            .test_report(&manifest.project.out_paths().test_report)
+            .test_report(test_report)
             .args(&["--details", "tree", "--disable-banner"])
             .args(&["--exclude-engine", "junit-vintage"])
             .args(&["--exclude-engine", "junit-platform-suite"]);
@@ -120,3 +162,32 @@ impl<'a> Test<'_> {
         java
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::testcases;
+
+    #[test]
+    fn testcases_of_a_junit_report() {
+        let report = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuite name="JUnit Jupiter" tests="3">
+  <testcase name="adds()" classname="MathTest" time="0.012"/>
+  <testcase name="divides()" classname="MathTest" time="0.003">
+    <failure message="expected: &lt;2&gt; but was: &lt;1&gt;" type="org.opentest4j.AssertionFailedError"/>
+  </testcase>
+  <testcase name="later()" classname="MathTest" time="0">
+    <skipped/>
+  </testcase>
+</testsuite>"#;
+
+        let tests = testcases(report);
+
+        assert_eq!(tests.len(), 3);
+        assert_eq!(tests[0]["outcome"], "passed");
+        assert_eq!(tests[0]["duration_ms"], 12.0);
+        assert_eq!(tests[1]["outcome"], "failed");
+        assert_eq!(tests[1]["message"], "expected: <2> but was: <1>");
+        assert_eq!(tests[2]["class"], "MathTest");
+        assert_eq!(tests[2]["outcome"], "skipped");
+    }
+}
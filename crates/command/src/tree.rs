@@ -1,3 +1,4 @@
+use std::collections::BTreeSet;
 use std::fmt::Display;
 use std::path::{Path, PathBuf};
 
@@ -64,7 +65,7 @@ impl<'a> Tree {
 
         self.files
             .iter()
-            .filter(|path| path.extension().unwrap_or_default() == "kt")
+            .filter(|path| is_source(path))
             .map(Path::new)
             .map(HeaderKt::parse)
             .filter_map(Result::ok)
@@ -83,6 +84,46 @@ impl<'a> Tree {
 
         Ok(sorted)
     }
+
+    /// The changed files together with every file that (transitively) depends on them,
+    /// either through an import or by sharing a package.
+    pub fn affected(&self, changed: &[PathBuf], removed_packages: &[String]) -> Vec<PathBuf> {
+        let headers = self.files
+            .iter()
+            .filter(|path| is_source(path))
+            .filter_map(|path| HeaderKt::parse(path).ok())
+            .collect::<Vec<_>>();
+
+        let mut affected = changed.iter().cloned().collect::<BTreeSet<_>>();
+        let mut packages = removed_packages.iter().cloned().collect::<BTreeSet<_>>();
+
+        loop {
+            packages.extend(headers
+                .iter()
+                .filter(|header| affected.contains(&header.file))
+                .map(|header| header.package.clone()));
+
+            let dependents = headers
+                .iter()
+                .filter(|header| !affected.contains(&header.file))
+                .filter(|header| packages.contains(&header.package) || header.imports.iter().any(|it| packages.contains(it)))
+                .map(|header| header.file.clone())
+                .collect::<Vec<_>>();
+
+            if dependents.is_empty() {
+                break;
+            }
+
+            affected.extend(dependents);
+        }
+
+        affected.into_iter().collect()
+    }
+}
+
+/// Kotlin and Java sources, kotlinc resolves symbols in both.
+fn is_source(path: &Path) -> bool {
+    matches!(path.extension().and_then(|it| it.to_str()), Some("kt" | "java"))
 }
 
 #[derive(Clone, Default, Debug)]
@@ -100,14 +141,16 @@ impl HeaderKt {
         let mut package = String::new();
         let mut imports = Vec::new();
 
-        for line in content.lines() {
+        for line in content.lines().map(str::trim) {
             match line {
                 line if line.starts_with("package ") => {
-                    package = line.replace("package ", "").replace_after_last(".")
+                    package = line.replace("package ", "").trim_end_matches(';').to_owned()
                 }
                 line if line.starts_with("import ") => {
                     imports.push(line.replace("import ", "").replace_after_last("."))
                 }
+                line if line.starts_with("//") || line.starts_with("/*") || line.starts_with('*') => {}
+                line if line.starts_with("@file:") => {}
                 "" => {}
                 _ => break, // skip rest of file
             }
@@ -0,0 +1,74 @@
+use anyhow::Context;
+use dependency::version::Upgrade;
+use dependency::Package;
+use manifest::config::BuildK;
+use manifest::edit::ManifestEditor;
+use manifest::Manifest;
+use util::buildk_output::{BuildkOutput, Failure};
+use util::PartialConclusion;
+
+use crate::fetch::Fetch;
+use crate::outdated::available_versions;
+use crate::Command;
+
+pub(crate) struct Update<'a> {
+    buildk: &'a BuildK,
+    pre_releases: bool,
+}
+
+impl<'a> Command for Update<'a> {
+    type Item = Upgrade;
+
+    fn execute(&mut self, arg: Option<Self::Item>) -> BuildkOutput {
+        let mut output = BuildkOutput::new("update");
+        let upgrade = arg.unwrap_or(Upgrade::Minor);
+
+        if let Err(err) = self.update(upgrade, &mut output) {
+            eprintln!("{err:#}");
+            output.conclude(PartialConclusion::FAILED).stderr(format!("{err:#}"));
+        }
+
+        output
+    }
+}
+
+impl<'a> Update<'a> {
+    pub fn new(buildk: &'a BuildK, pre_releases: bool) -> Update<'a> {
+        Update { buildk, pre_releases }
+    }
+
+    /// Rewrite the versions in buildk.toml to the newest allowed by `upgrade`, then fetch them.
+    fn update(&mut self, upgrade: Upgrade, output: &mut BuildkOutput) -> anyhow::Result<()> {
+        let manifest = self.buildk.manifest.as_ref().context("no buildk.toml found.")?;
+        let mut editor = ManifestEditor::open(&manifest.dir)?;
+
+        let (versions, failures) = available_versions(self.buildk, &editor.declared());
+        let mut updated = vec![];
+        for (pkg, available) in versions {
+            let Some(version) = upgrade.select(&pkg.version, &available, self.pre_releases) else {
+                continue;
+            };
+            editor.add(&Package::new(pkg.name.clone(), pkg.namespace.clone(), version.clone(), pkg.kind.clone()))?;
+            updated.push(format!("{} -> {version}", pkg.coordinates()));
+        }
+
+        if updated.is_empty() {
+            output.stdout("all dependencies are up to date".into());
+        } else {
+            editor.save()?;
+            output.stdout(updated.join("\n"));
+
+            let buildk = BuildK {
+                manifest: Some(Manifest::try_from_dir(&manifest.dir)?),
+                ..self.buildk.clone()
+            };
+            output.apply(Fetch::new(&buildk, None, false, None).execute(None));
+        }
+
+        if !failures.is_empty() {
+            output.fail(Failure::Network).stderr(failures.join("\n"));
+        }
+        output.conclude(PartialConclusion::SUCCESS);
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::Context;
+use dependency::{resolver, Package, PackageKind};
+use manifest::config::BuildK;
+use termtree::Tree;
+use util::buildk_output::{BuildkOutput, Failure};
+use util::colorize::Colorize;
+use util::PartialConclusion;
+
+use crate::{failed, Command};
+
+pub(crate) struct Why<'a> {
+    buildk: &'a BuildK,
+}
+
+impl<'a> Command for Why<'a> {
+    type Item = String;
+
+    fn execute(&mut self, arg: Option<Self::Item>) -> BuildkOutput {
+        let mut output = BuildkOutput::new("why");
+        let Some(artifact) = arg else {
+            return failed("why", Failure::Config, "an artifact is required");
+        };
+
+        match self.why(&artifact) {
+            Ok(insight) => output.conclude(PartialConclusion::SUCCESS).stdout(insight),
+            Err(err) => {
+                eprintln!("{err:#}");
+                output.conclude(PartialConclusion::FAILED).stderr(format!("{err:#}"))
+            }
+        };
+
+        output
+    }
+}
+
+impl<'a> Why<'a> {
+    pub fn new(buildk: &'a BuildK) -> Why<'a> {
+        Why { buildk }
+    }
+
+    /// Every path from a declared dependency to `<namespace>:<name>[:<version>]`, or `<name>` in any namespace.
+    /// With a version, only the paths requesting that version.
+    fn why(&self, artifact: &str) -> anyhow::Result<String> {
+        let manifest = self.buildk.manifest.as_ref().context("no buildk.toml found.")?;
+
+        let (namespace, name, version) = match artifact.split(':').collect::<Vec<_>>()[..] {
+            [name] => (None, name, None),
+            [namespace, name] => (Some(namespace), name, None),
+            [namespace, name, version] => (Some(namespace), name, Some(version)),
+            _ => anyhow::bail!("expected <namespace>:<name>[:<version>], got {artifact}"),
+        };
+        let target = |pkg: &Package| {
+            pkg.name == name
+                && (namespace.is_none() || pkg.namespace.as_deref() == namespace)
+                && (version.is_none() || Some(pkg.version.as_str()) == version)
+        };
+
+        let kinds = [PackageKind::Compile, PackageKind::Runtime, PackageKind::Test];
+        let resolution = manifest.resolve(&kinds);
+        let paths = resolver::paths(&manifest.roots(&kinds), &resolution.selected, target, Package::transitives);
+        anyhow::ensure!(!paths.is_empty(), "{artifact} is not in the dependency graph, is it fetched?");
+
+        let mut insight = vec![];
+        for selected in resolution.selected.iter().filter(|pkg| pkg.kind != PackageKind::Pom) {
+            let mut requested = paths
+                .iter()
+                .filter_map(|path| path.last())
+                .filter(|pkg| target(pkg) && pkg.namespace == selected.namespace && pkg.name == selected.name)
+                .map(|pkg| pkg.version.clone())
+                .collect::<Vec<_>>();
+            if requested.is_empty() {
+                continue;
+            }
+            requested.sort_by(|a, b| dependency::version::compare(a, b));
+            requested.dedup();
+            insight.push(format!("{} selected, requested {}", selected.coordinates().as_green(), requested.join(", ")));
+        }
+
+        // a path not ending at the artifact ends at a package whose paths were printed above, marked (*) like Gradle
+        for path in paths.iter() {
+            let shown = path.last().is_some_and(|pkg| !target(pkg));
+            insight.push(format!("\n{}", self.tree(path, &resolution.selected, shown)).trim_end().to_owned());
+        }
+
+        Ok(insight.join("\n"))
+    }
+
+    /// `root -> ... -> target` as a tree, every package with its requested and selected version and its scope on this path.
+    fn tree(&self, path: &[Package], selected: &[Package], shown: bool) -> Tree<String> {
+        // a test dependency pulls everything below it into test, a runtime one into runtime
+        let mut scope = PackageKind::Compile;
+        let label = |pkg: &Package| {
+            scope = scope.clone().max(pkg.kind.clone());
+            let mut label = pkg.coordinates();
+            if let Some(winner) = selected.iter().find(|it| it.namespace == pkg.namespace && it.name == pkg.name) {
+                if winner.version != pkg.version {
+                    label.push_str(&format!(" -> {}", winner.version).as_yellow());
+                }
+            }
+            let scope = Into::<String>::into(scope.clone()).to_lowercase();
+            format!("{label} {}", format!("({scope})").as_gray())
+        };
+        let mut labels = path.iter().map(label).collect::<Vec<_>>();
+        if let (true, Some(last)) = (shown, labels.last_mut()) {
+            last.push_str(" (*)");
+        }
+
+        labels
+            .into_iter()
+            .rev()
+            .fold(None, |child: Option<Tree<String>>, label| {
+                let tree = Tree::new(label);
+                Some(match child {
+                    Some(child) => tree.with_leaves([child]),
+                    None => tree,
+                })
+            })
+            .unwrap_or_else(|| Tree::new(String::new()))
+    }
+}
@@ -1,23 +1,30 @@
 use std::collections::BTreeSet;
 use std::fmt::Display;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
+use local::CachedFile;
+
+pub mod local;
 pub mod parser;
+pub mod resolver;
+pub mod version;
 
 pub trait Parser<T>
 where
     T: Ord,
 {
-    fn parse(path: PathBuf, kind: PackageKind) -> BTreeSet<T>;
+    /// `maven_local` is the local Maven repository parent poms and boms missing from the cache are read from.
+    fn parse(path: PathBuf, kind: PackageKind, maven_local: Option<&Path>) -> BTreeSet<T>;
 }
 
-pub fn resolve_descriptor(path: &Path) -> Option<PathBuf> {
-    let gradle_descriptor = path.join("maven.xml");
+pub fn resolve_descriptor(path: &Path, maven_local: Option<&Path>) -> Option<PathBuf> {
+    let gradle_descriptor = local::file(path, CachedFile::Pom, maven_local);
     if gradle_descriptor.exists() {
         return Some(gradle_descriptor);
     }
 
-    let maven_descriptor = path.join("gradle.json");
+    let maven_descriptor = local::file(path, CachedFile::Module, maven_local);
     if maven_descriptor.exists() {
         return Some(maven_descriptor);
     }
@@ -25,6 +32,7 @@ pub fn resolve_descriptor(path: &Path) -> Option<PathBuf> {
     None
 }
 
+#[cfg(not(test))]
 pub fn cache_location() -> PathBuf {
     home::home_dir()
         .expect("home directory")
@@ -32,13 +40,52 @@ pub fn cache_location() -> PathBuf {
         .join("cache")
 }
 
-#[derive(Ord, PartialOrd, Eq, PartialEq, Clone)]
+/// Each test thread gets an empty cache of its own, removed when the test ends.
+#[cfg(test)]
+pub fn cache_location() -> PathBuf {
+    thread_local! {
+        static CACHE: tempfile::TempDir = tempfile::tempdir().expect("test cache");
+    }
+    CACHE.with(|it| it.path().to_path_buf())
+}
+
+#[derive(Clone, Debug)]
 pub struct Package {
     pub name: String,
     pub namespace: Option<String>,
     pub version: String,
     pub kind: PackageKind,
     pub location: PathBuf,
+    pub exclusions: Vec<Exclusion>,
+    /// The local Maven repository files missing from the cache are read from, when the manifest declares `mavenLocal`
+    pub maven_local: Option<PathBuf>,
+}
+
+// where its files are read from doesn't make it another package
+impl Package {
+    fn key(&self) -> (&String, &Option<String>, &String, &PackageKind, &PathBuf, &Vec<Exclusion>) {
+        (&self.name, &self.namespace, &self.version, &self.kind, &self.location, &self.exclusions)
+    }
+}
+
+impl PartialEq for Package {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Package {}
+
+impl PartialOrd for Package {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Package {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
 }
 
 impl Package {
@@ -48,9 +95,14 @@ impl Package {
         version: String,
         kind: PackageKind,
     ) -> Self {
-        let location = match &namespace {
-            Some(ns) => cache_location().join(&ns).join(&name).join(&version),
-            None => cache_location().join(&name).join(&version),
+        let module = match &namespace {
+            Some(ns) => cache_location().join(&ns).join(&name),
+            None => cache_location().join(&name),
+        };
+        // a range is resolved against the maven-metadata.xml in the module directory
+        let location = match version::is_dynamic(&version) {
+            true => module,
+            false => module.join(&version),
         };
 
         Package {
@@ -59,37 +111,95 @@ impl Package {
             version,
             kind,
             location,
+            exclusions: vec![],
+            maven_local: None,
         }
     }
+
+    /// `namespace:name:version`
+    pub fn coordinates(&self) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{}:{}:{}", namespace, self.name, self.version),
+            None => format!("{}:{}", self.name, self.version),
+        }
+    }
+
+    /// A version range or dynamic version like `latest.release`, see [version::Requirement].
+    pub fn is_dynamic(&self) -> bool {
+        version::is_dynamic(&self.version)
+    }
+
+    /// Pick the highest version matching a range or dynamic version from the downloaded maven-metadata.xml.
+    /// The package stays dynamic until the metadata is downloaded.
+    pub fn resolve_version(self) -> Package {
+        let selected = match version::Requirement::from_str(&self.version) {
+            Ok(requirement) if requirement.is_dynamic() => {
+                requirement.select(&version::available(&self.location, self.maven_local.as_deref()))
+            }
+            _ => None,
+        };
+
+        match selected {
+            Some(version) => Package::new(self.name, self.namespace, version, self.kind)
+                .exclude(self.exclusions)
+                .maven_local(self.maven_local),
+            None => self,
+        }
+    }
+
+    /// Keep these transitives out of the graph below this package.
+    pub fn exclude(mut self, exclusions: Vec<Exclusion>) -> Self {
+        self.exclusions = exclusions;
+        self
+    }
+
+    /// Read files missing from the cache from this local Maven repository, its transitives too.
+    pub fn maven_local(mut self, repository: Option<PathBuf>) -> Self {
+        self.maven_local = repository;
+        self
+    }
+
     // todo: if one transitive dep has previously failed, this is not good enough for a check
+    /// An empty jar or descriptor is what an interrupted download leaves behind.
     pub fn is_cached(&self) -> bool {
-        let jar = self.jar_absolute_path();
-        let descriptor = resolve_descriptor(&self.location);
+        let not_empty = |path: &Path| path.metadata().is_ok_and(|it| it.len() > 0);
 
+        let jar = self.jar_absolute_path();
         if !jar.exists() {
-            return false;
-        } else if jar.metadata().unwrap().len() == 0 {
-            println!("Jar found {}, but was empty", self.name);
-            return false;
+            return self.is_bom();
         }
 
-        if descriptor.is_none() {
-            println!("No descriptor found for {}", self.name);
-            return false;
-        } else if descriptor.unwrap().metadata().unwrap().len() == 0 {
-            println!("Desciptor found {}, but was empty", self.name);
-            return false;
-        }
+        not_empty(&jar)
+            && resolve_descriptor(&self.location, self.maven_local.as_deref()).is_some_and(|it| not_empty(&it))
+    }
 
-        true
+    /// Packaged as a pom without a jar, e.g. a bom only managing versions.
+    pub fn is_bom(&self) -> bool {
+        parser::maven::packaging(&self.location, self.maven_local.as_deref()).as_deref() == Some("pom")
     }
-    
+
     pub fn transitives(&self) -> Vec<Package> {
-        parser::parse(&self.location, self.kind.clone()).into_iter().collect()
+        if self.is_dynamic() {
+            return vec![];
+        }
+
+        parser::parse(&self.location, self.kind.clone(), self.maven_local.as_deref())
+            .into_iter()
+            .map(|pkg| pkg.maven_local(self.maven_local.clone()).resolve_version())
+            .collect()
     }
 
+    /// The jar in the cache, or in the local Maven repository when it is only there.
     pub fn jar_absolute_path(&self) -> PathBuf {
-        self.location.join("pkg.jar")
+        local::file(&self.location, CachedFile::Jar, self.maven_local.as_deref())
+    }
+
+    /// Name of the repository the package was downloaded from, recorded by the client.
+    pub fn repository(&self) -> Option<String> {
+        std::fs::read_to_string(self.location.join("repository"))
+            .ok()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
     }
 
     pub fn classpath(&self) -> String {
@@ -123,18 +233,61 @@ impl Into<String> for PackageKind {
             PackageKind::Compile => "Compile",
             PackageKind::Runtime => "Runtime",
             PackageKind::Test => "Test",
+            PackageKind::Pom => "Pom",
         }
         .to_string()
     }
 }
 
 
+/// An excluded transitive, `*` matches any namespace or name.
+#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Debug)]
+pub struct Exclusion {
+    pub namespace: String,
+    pub name: String,
+}
+
+impl Exclusion {
+    pub fn new(namespace: String, name: String) -> Self {
+        Exclusion { namespace, name }
+    }
+
+    pub fn matches(&self, pkg: &Package) -> bool {
+        let namespace = pkg.namespace.as_deref().unwrap_or_default();
+        (self.namespace == "*" || self.namespace == namespace) && (self.name == "*" || self.name == pkg.name)
+    }
+}
+
+/// `<namespace>_<name>` or `<namespace>:<name>`, like the keys in buildk.toml, or just `<name>` for any namespace.
+/// Only the first separator counts, the name may contain `_`.
+impl FromStr for Exclusion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':').or_else(|| s.split_once('_')) {
+            None if !s.is_empty() => Ok(Exclusion::new("*".into(), s.to_string())),
+            Some((namespace, name)) if !namespace.is_empty() && !name.is_empty() => {
+                Ok(Exclusion::new(namespace.to_string(), name.to_string()))
+            }
+            _ => anyhow::bail!("Invalid exclusion: {} (expected <namespace>_<name>)", s),
+        }
+    }
+}
+
+impl Display for Exclusion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}_{}", self.namespace, self.name)
+    }
+}
+
 #[derive(Ord, PartialOrd, Eq, PartialEq, Default, Clone, Debug)]
 pub enum PackageKind {
     #[default]
     Compile,
     Runtime,
     Test,
+    /// Parent poms and imported boms, only needed to read other descriptors
+    Pom,
 }
 
 #[derive(Ord, PartialOrd, Eq, PartialEq, Default, Debug)]
@@ -0,0 +1,118 @@
+//! Packages missing from `~/.buildk/cache` are read from the Maven layout of the local repository,
+//! e.g. `~/.m2/repository`, when the manifest declares a `mavenLocal` repo, see [crate::Package::maven_local].
+
+use std::path::{Path, PathBuf};
+
+use crate::cache_location;
+
+/// A file of a cached package, named differently in the cache and the Maven layout.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CachedFile {
+    Jar,
+    Pom,
+    Module,
+    Sources,
+}
+
+impl CachedFile {
+    pub const ALL: [CachedFile; 4] = [CachedFile::Jar, CachedFile::Pom, CachedFile::Module, CachedFile::Sources];
+
+    /// The name in `~/.buildk/cache/<group>/<artifact>/<version>`
+    pub fn cached_name(&self) -> &'static str {
+        match self {
+            CachedFile::Jar => "pkg.jar",
+            CachedFile::Pom => "maven.xml",
+            CachedFile::Module => "gradle.json",
+            CachedFile::Sources => "sources.jar",
+        }
+    }
+
+    /// The name in `<repository>/<group path>/<artifact>/<version>`
+    fn maven_name(&self, artifact: &str, version: &str) -> String {
+        match self {
+            CachedFile::Jar => format!("{artifact}-{version}.jar"),
+            CachedFile::Pom => format!("{artifact}-{version}.pom"),
+            CachedFile::Module => format!("{artifact}-{version}.module"),
+            CachedFile::Sources => format!("{artifact}-{version}-sources.jar"),
+        }
+    }
+}
+
+/// The file in the cache directory `location`, or the same file in the local Maven repository when it is only there.
+pub fn file(location: &Path, file: CachedFile, repository: Option<&Path>) -> PathBuf {
+    let cached = location.join(file.cached_name());
+    if cached.exists() {
+        return cached;
+    }
+
+    match repository.and_then(|repository| maven_file(location, file, repository)) {
+        Some(local) if local.exists() => local,
+        _ => cached,
+    }
+}
+
+/// `~/.buildk/cache/<group>/<artifact>[/<version>]` -> `<repository>/<group path>/<artifact>[/<version>]`
+pub fn dir(location: &Path, repository: &Path) -> Option<PathBuf> {
+    let relative = location.strip_prefix(cache_location()).ok()?;
+    let mut components = relative.iter().map(|it| it.to_string_lossy().to_string());
+
+    let group = components.next()?;
+    let mut dir = repository.join(group.replace('.', "/"));
+    for component in components {
+        dir = dir.join(component);
+    }
+    Some(dir)
+}
+
+fn maven_file(location: &Path, file: CachedFile, repository: &Path) -> Option<PathBuf> {
+    let version = location.file_name()?.to_string_lossy().to_string();
+    let artifact = location.parent()?.file_name()?.to_string_lossy().to_string();
+    Some(dir(location, repository)?.join(file.maven_name(&artifact, &version)))
+}
+
+/// Copy the files downloaded to the cache directory `location` into the local Maven `repository`.
+pub fn install(location: &Path, repository: &Path) -> std::io::Result<()> {
+    for file in CachedFile::ALL {
+        let cached = location.join(file.cached_name());
+        let Some(target) = maven_file(location, file, repository) else {
+            return Ok(());
+        };
+
+        if cached.exists() && !target.exists() {
+            if let Some(dir) = target.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::copy(&cached, &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dir, CachedFile};
+    use crate::{cache_location, local, Package, PackageKind};
+
+    #[test]
+    fn falls_back_to_maven_layout() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repository = tmp.path().join("repository");
+
+        let location = cache_location().join("org.example").join("example").join("1.0");
+        let m2 = repository.join("org/example/example/1.0");
+        assert_eq!(dir(&location, &repository), Some(m2.clone()));
+
+        std::fs::create_dir_all(&m2).unwrap();
+        std::fs::write(m2.join("example-1.0.pom"), "<project/>").unwrap();
+        assert_eq!(local::file(&location, CachedFile::Pom, Some(&repository)), m2.join("example-1.0.pom"));
+        assert_eq!(local::file(&location, CachedFile::Pom, None), location.join("maven.xml"));
+        assert_eq!(local::file(&location, CachedFile::Jar, Some(&repository)), location.join("pkg.jar"));
+
+        std::fs::write(m2.join("example-1.0.jar"), "jar").unwrap();
+        let pkg = Package::new("example".into(), Some("org.example".into()), "1.0".into(), PackageKind::Compile);
+        assert!(!pkg.is_cached());
+        let pkg = pkg.maven_local(Some(repository.clone()));
+        assert_eq!(pkg.jar_absolute_path(), m2.join("example-1.0.jar"));
+        assert!(pkg.is_cached());
+    }
+}
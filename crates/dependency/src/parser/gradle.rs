@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use std::{collections::BTreeSet, fs, path::PathBuf};
+use std::{collections::BTreeSet, fs, path::{Path, PathBuf}};
 
 use crate::{Package, PackageKind, Parser};
 pub struct GradleParser;
@@ -26,7 +26,7 @@ pub enum GradleVariant {
 
 // https://docs.gradle.org/current/userguide/dependency_management.html#sec:how-gradle-downloads-deps
 impl Parser<Package> for GradleParser {
-    fn parse(path: PathBuf, kind: PackageKind) -> BTreeSet<Package> {
+    fn parse(path: PathBuf, kind: PackageKind, _maven_local: Option<&Path>) -> BTreeSet<Package> {
         let content = match fs::read_to_string(path) {
             Ok(content) => content,
             Err(_) => return BTreeSet::default(),
@@ -60,11 +60,16 @@ impl From<gradle::Descriptor> for BTreeSet<Package> {
             deps.insert(component.into());
         }
 
-        // dependencies
+        // dependencies, without a version they are managed by a platform buildk does not read
         for variant in value.variants {
             if let Some(dependencies) = variant.dependencies {
                 for dep in dependencies {
-                    deps.insert(dep.into());
+                    match Package::try_from(dep) {
+                        Ok(pkg) => {
+                            deps.insert(pkg);
+                        }
+                        Err(err) => eprintln!("{err}"),
+                    }
                 }
             }
         }
@@ -96,7 +101,9 @@ impl From<GradlePackage> for Package {
 mod gradle {
     use serde::{Deserialize, Serialize};
     use std::collections::BTreeMap;
+    use std::str::FromStr;
 
+    use crate::version::Requirement;
     use crate::Package;
 
     #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize)]
@@ -187,7 +194,7 @@ mod gradle {
     pub(crate) struct Dep {
         group: String,
         module: String,
-        version: Version,
+        version: Option<Version>,
         excludes: Option<Vec<Exclusion>>,
         attributes: Option<BTreeMap<String, String>>,
         requested_capabilities: Option<Vec<Capability>>,
@@ -199,77 +206,60 @@ mod gradle {
         module: String,
     }
 
-    impl From<Dep> for Package {
-        fn from(value: Dep) -> Self {
-            Package::new(
+    impl TryFrom<Dep> for Package {
+        type Error = String;
+
+        fn try_from(value: Dep) -> Result<Self, Self::Error> {
+            let Some(version) = value.version() else {
+                return Err(format!("skipping {}:{}, it has no version", value.group, value.module));
+            };
+
+            let pkg = Package::new(
                 value.module.clone(),
                 Some(value.group.clone()),
-                value.version().clone(),
+                version,
                 Default::default(), // TODO: resolve variant
             )
+            .exclude(
+                value
+                    .excludes
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|it| crate::Exclusion::new(it.group, it.module))
+                    .collect(),
+            );
+            Ok(pkg)
         }
     }
 
     impl Dep {
-        fn version(&self) -> String {
-            if let Some(version) = &self.version.requires {
-                match version {
-                    VersionKind::Single(version) => return version.to_owned(),
-                    VersionKind::Array(versions) => {
-                        // TODO: skip last while self.version.rejects.contains(last)
-                        if let Some(last) = versions.last() {
-                            return last.to_owned();
-                        }
-                    }
-                }
-            }
-
-            if let Some(version) = &self.version.prefers {
-                match version {
-                    VersionKind::Single(version) => return version.to_owned(),
-                    VersionKind::Array(versions) => {
-                        // TODO: skip last while self.version.rejects.contains(last)
-                        if let Some(last) = versions.last() {
-                            return last.to_owned();
-                        }
-                    }
+        /// `strictly` wins over `requires`, `prefers` is taken when it satisfies them.
+        /// Ranges are kept as is and resolved against the available versions.
+        /// `None` when no version is given, e.g. when a platform manages it.
+        fn version(&self) -> Option<String> {
+            let version = self.version.as_ref()?;
+            let required = version.strictly.as_ref().or(version.requires.as_ref()).map(VersionKind::last);
+            let preferred = version.prefers.as_ref().map(VersionKind::last);
+
+            match (required, preferred) {
+                (Some(required), Some(preferred))
+                    if Requirement::from_str(&required).is_ok_and(|it| it.matches(&preferred)) =>
+                {
+                    Some(preferred)
                 }
+                (Some(required), _) => Some(required),
+                (None, preferred) => preferred,
             }
-
-            if let Some(version) = &self.version.strictly {
-                match version {
-                    VersionKind::Single(version) => return version.to_owned(),
-                    VersionKind::Array(versions) => {
-                        // TODO: skip last while self.version.rejects.contains(last)
-                        if let Some(last) = versions.last() {
-                            return last.to_owned();
-                        }
-                    }
-                }
-            }
-
-            panic!("unable to determine version at this time")
         }
+    }
 
-        fn versions(&self) -> Vec<String> {
-            if let Some(version) = &self.version.requires {
-                if let VersionKind::Array(versions) = version {
-                    return versions.to_owned();
-                }
-            }
-
-            if let Some(version) = &self.version.prefers {
-                if let VersionKind::Array(versions) = version {
-                    return versions.to_owned();
-                }
-            }
-            if let Some(version) = &self.version.strictly {
-                if let VersionKind::Array(versions) = version {
-                    return versions.to_owned();
-                }
+    impl VersionKind {
+        // TODO: skip last while self.version.rejects.contains(last)
+        fn last(&self) -> String {
+            match self {
+                VersionKind::Single(version) => version.to_owned(),
+                VersionKind::Array(versions) => versions.last().cloned().unwrap_or_default(),
             }
-
-            vec![]
         }
     }
 
@@ -397,4 +387,29 @@ mod tests {
 
         assert_eq!(dependencies.len(), 3);
     }
+
+    #[test]
+    fn skips_dependencies_without_a_version() {
+        let content = r#"
+{
+    "formatVersion": "1.1",
+    "variants": [
+        {
+            "name": "apiElements",
+            "dependencies": [
+                { "group": "org.example", "module": "bom", "attributes": { "org.gradle.category": "platform" } },
+                { "group": "org.example", "module": "managed" },
+                { "group": "org.example", "module": "lib", "version": { "requires": "1.0" } }
+            ]
+        }
+    ]
+}
+        "#;
+
+        let descriptor: Descriptor = serde_json::from_str(content).expect("unable to parse gradle module descriptor");
+        let dependencies: BTreeSet<Package> = descriptor.into();
+
+        let names = dependencies.iter().map(|pkg| pkg.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["lib"]);
+    }
 }
@@ -1,17 +1,18 @@
 use std::collections::{BTreeSet, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use roxmltree::Node;
 
 use util::sub_strings::SubStrings;
 
-use crate::{Package, PackageKind, Parser};
+use crate::local::{self, CachedFile};
+use crate::{Exclusion, Package, PackageKind, Parser};
 
 pub struct MavenParser;
 
 impl Parser<Package> for MavenParser {
-    fn parse(path: PathBuf, kind: PackageKind) -> BTreeSet<Package> {
+    fn parse(path: PathBuf, kind: PackageKind, maven_local: Option<&Path>) -> BTreeSet<Package> {
         let content = match fs::read_to_string(path) {
             Ok(content) => content,
             Err(_) => return BTreeSet::default(),
@@ -21,33 +22,189 @@ impl Parser<Package> for MavenParser {
         let root = doc.root();
         let project = node(&root, "project").expect("invalid pom, missing <project> tag");
 
-        let props = project.parse_props();
-        let deps = project.parse_deps();
-        let deps = interpolate(deps, &props);
-        let managed = project.parse_managed_deps();
-        let managed = interpolate(managed, &props);
+        let pom = Effective::new(project, maven_local);
+        let deps = interpolate(pom.deps, &pom.props);
+        let managed = interpolate(pom.managed, &pom.props);
+
+        // managed dependencies only contribute versions, imported boms are needed to know them
+        let imports = imports(&managed);
+        let versions = managed_versions(&managed, maven_local, &mut BTreeSet::new());
 
         let mut unique_deps = BTreeSet::new();
 
         deps.into_iter()
+            .map(|mut it| {
+                if it.version.is_none() {
+                    it.version = versions.get(&(it.group.clone(), it.artifact.clone())).cloned();
+                }
+                it
+            })
             .filter(|it| it.version.is_some())
             .filter(|it| kind == it.scope.clone().into())
             .for_each(|it| {
                 unique_deps.insert(it);
             });
 
-        managed
+        unique_deps
             .into_iter()
-            .filter(|it| it.version.is_some())
-            .filter(|it| kind == it.scope.clone().into())
-            .for_each(|it| {
-                unique_deps.insert(it);
-            });
+            .map(Package::from)
+            .chain(imports)
+            .chain(pom.parents)
+            .collect()
+    }
+}
+
+/// A pom merged with its parent chain, as far as the parents are downloaded.
+/// Declarations in the child override the ones inherited from its parents.
+struct Effective {
+    props: HashMap<String, String>,
+    deps: Vec<Artifact>,
+    managed: Vec<Artifact>,
+    parents: Vec<Package>,
+}
+
+impl Effective {
+    /// Parents missing from the cache are read from `maven_local`.
+    fn new(project: Node, maven_local: Option<&Path>) -> Self {
+        let parent = project.parse_parent();
+
+        let mut props = project.parse_props();
+        let group = node_text(&project, "groupId").or_else(|| parent.as_ref().and_then(|it| it.namespace.clone()));
+        let version = node_text(&project, "version").or_else(|| parent.as_ref().map(|it| it.version.clone()));
+        let builtins = [
+            ("project.groupId", group),
+            ("project.artifactId", node_text(&project, "artifactId")),
+            ("project.version", version.clone()),
+            ("pom.version", version.clone()),
+            ("version", version),
+            ("project.parent.groupId", parent.as_ref().and_then(|it| it.namespace.clone())),
+            ("project.parent.artifactId", parent.as_ref().map(|it| it.name.clone())),
+            ("project.parent.version", parent.as_ref().map(|it| it.version.clone())),
+        ];
+        for (key, value) in builtins {
+            if let Some(value) = value {
+                props.entry(key.to_owned()).or_insert(value);
+            }
+        }
+
+        let mut pom = Effective {
+            props,
+            deps: project.parse_deps(),
+            managed: project.parse_managed_deps(),
+            parents: vec![],
+        };
+
+        let mut next = parent;
+        while let Some(parent) = next.take() {
+            if pom.parents.contains(&parent) {
+                break;
+            }
+            pom.parents.push(parent.clone());
 
-        unique_deps.into_iter().map(Package::from).collect()
+            let Ok(content) = fs::read_to_string(local::file(&parent.location, CachedFile::Pom, maven_local)) else {
+                break;
+            };
+            let Ok(doc) = roxmltree::Document::parse(&content) else {
+                break;
+            };
+            let root = doc.root();
+            let Some(project) = node(&root, "project") else {
+                break;
+            };
+
+            for (key, value) in project.parse_props() {
+                pom.props.entry(key).or_insert(value);
+            }
+            inherit(&mut pom.deps, project.parse_deps());
+            inherit(&mut pom.managed, project.parse_managed_deps());
+            next = project.parse_parent();
+        }
+
+        pom
+    }
+}
+
+fn inherit(artifacts: &mut Vec<Artifact>, inherited: Vec<Artifact>) {
+    for artifact in inherited {
+        if !artifacts.iter().any(|it| it.group == artifact.group && it.artifact == artifact.artifact) {
+            artifacts.push(artifact);
+        }
     }
 }
 
+/// The versions managed by the pom in `location`, including the boms it imports.
+pub fn managed(location: &Path, maven_local: Option<&Path>) -> Vec<Package> {
+    managed_in(location, maven_local, &mut BTreeSet::new())
+        .into_iter()
+        .map(|((group, artifact), version)| {
+            Package::new(artifact, Some(group), version, PackageKind::default()).maven_local(maven_local.map(Path::to_path_buf))
+        })
+        .collect()
+}
+
+/// The `<packaging>` of the pom in `location`, `pom` for boms and parents.
+pub fn packaging(location: &Path, maven_local: Option<&Path>) -> Option<String> {
+    let content = fs::read_to_string(local::file(location, CachedFile::Pom, maven_local)).ok()?;
+    let doc = roxmltree::Document::parse(&content).ok()?;
+    let root = doc.root();
+    let project = node(&root, "project")?;
+    node_text(&project, "packaging")
+}
+
+type ManagedVersions = HashMap<(String, String), String>;
+
+fn managed_in(location: &Path, maven_local: Option<&Path>, visited: &mut BTreeSet<PathBuf>) -> ManagedVersions {
+    if !visited.insert(location.to_path_buf()) {
+        return HashMap::new();
+    }
+
+    let content = match fs::read_to_string(local::file(location, CachedFile::Pom, maven_local)) {
+        Ok(content) => content,
+        Err(_) => return HashMap::new(),
+    };
+
+    let doc = match roxmltree::Document::parse(&content) {
+        Ok(doc) => doc,
+        Err(_) => return HashMap::new(),
+    };
+
+    let root = doc.root();
+    match node(&root, "project") {
+        Some(project) => {
+            let pom = Effective::new(project, maven_local);
+            let managed = interpolate(pom.managed, &pom.props);
+            managed_versions(&managed, maven_local, visited)
+        }
+        None => HashMap::new(),
+    }
+}
+
+/// Versions declared directly win over the versions of imported boms, the first import wins over later ones.
+fn managed_versions(managed: &[Artifact], maven_local: Option<&Path>, visited: &mut BTreeSet<PathBuf>) -> ManagedVersions {
+    let mut versions = managed
+        .iter()
+        .filter(|it| !it.is_import())
+        .filter_map(|it| Some(((it.group.clone(), it.artifact.clone()), it.version.clone()?)))
+        .collect::<ManagedVersions>();
+
+    for bom in imports(managed) {
+        for (key, version) in managed_in(&bom.location, maven_local, visited) {
+            versions.entry(key).or_insert(version);
+        }
+    }
+
+    versions
+}
+
+fn imports(managed: &[Artifact]) -> Vec<Package> {
+    managed
+        .iter()
+        .filter(|it| it.is_import() && it.version.is_some())
+        .cloned()
+        .map(|it| Package::new(it.artifact, Some(it.group), it.version.unwrap_or_default(), PackageKind::Pom))
+        .collect()
+}
+
 // we need an internal state to keep track of the properties
 #[derive(Ord, PartialOrd, Eq, PartialEq, Default, Debug, Clone)]
 pub struct Artifact {
@@ -55,6 +212,8 @@ pub struct Artifact {
     artifact: String,
     version: Option<String>, // version may be a property, managed (defined elsewhere), or direct
     scope: String,
+    kind: Option<String>, // <type>, pom for boms
+    exclusions: Vec<Exclusion>,
     location: PathBuf,
 }
 
@@ -66,6 +225,7 @@ impl From<Artifact> for Package {
             artifact.version.expect("version missing"),
             artifact.scope.into(),
         )
+        .exclude(artifact.exclusions)
     }
 }
 
@@ -77,23 +237,43 @@ fn interpolate(artifacts: Vec<Artifact>, properties: &HashMap<String, String>) -
 }
 
 impl Artifact {
+    fn is_import(&self) -> bool {
+        self.scope == "import" && self.kind.as_deref() == Some("pom")
+    }
+
+    /// Unresolved versions are dropped.
     fn interpolate(&mut self, properties: &HashMap<String, String>) -> Self {
-        if let Some(version) = &self.version {
-            if version.contains("${") {
-                let property = version
-                    .clone()
-                    .substr_after('$')
-                    .remove_surrounding('{', '}');
-
-                if let Some(version) = properties.get(&property) {
-                    self.version = Some(version.to_owned());
-                }
-            }
-        }
+        self.group = expand(&self.group, properties);
+        self.version = self
+            .version
+            .as_ref()
+            .map(|version| expand(version, properties))
+            .filter(|version| !version.contains("${"));
         self.clone()
     }
 }
 
+/// Replace `${name}` references, a property may reference other properties.
+fn expand(value: &str, properties: &HashMap<String, String>) -> String {
+    let mut value = value.to_owned();
+    let mut from = 0;
+
+    for _ in 0..32 {
+        let Some(start) = value[from..].find("${").map(|it| it + from) else {
+            break;
+        };
+        let Some(end) = value[start..].find('}').map(|it| it + start) else {
+            break;
+        };
+        match properties.get(&value[start + 2..end]) {
+            Some(property) => value.replace_range(start..=end, property),
+            None => from = end + 1,
+        }
+    }
+
+    value
+}
+
 impl From<String> for PackageKind {
     fn from(value: String) -> Self {
         // TODO: provided scopes must be handled differently
@@ -108,6 +288,7 @@ impl From<String> for PackageKind {
             "runtime" => PackageKind::Runtime,
             "test" => PackageKind::Test,
             "system" => PackageKind::Compile,
+            "pom" => PackageKind::Pom,
             _ => PackageKind::default(),
         }
     }
@@ -116,6 +297,7 @@ impl From<String> for PackageKind {
 trait MavenXmlParser {
     fn parse_props(&self) -> HashMap<String, String>;
     fn parse_artifact(&self) -> Artifact;
+    fn parse_parent(&self) -> Option<Package>;
     fn parse_deps(&self) -> Vec<Artifact>;
     fn parse_managed_deps(&self) -> Vec<Artifact>;
 }
@@ -147,10 +329,29 @@ impl MavenXmlParser for Node<'_, '_> {
             artifact: node_text(self, "artifactId").expect("artifact_id"),
             version: node_text(self, "version"),
             scope: node_text(self, "scope").unwrap_or_else(|| "compile".to_owned()),
+            kind: node_text(self, "type"),
+            exclusions: match node(self, "exclusions") {
+                Some(exclusions) => exclusions
+                    .children()
+                    .filter(|node| node.is_element() && node.has_tag_name("exclusion"))
+                    .filter_map(|node| Some(Exclusion::new(node_text(&node, "groupId")?, node_text(&node, "artifactId")?)))
+                    .collect(),
+                None => vec![],
+            },
             ..Default::default()
         }
     }
 
+    fn parse_parent(&self) -> Option<Package> {
+        let parent = node(self, "parent")?;
+        Some(Package::new(
+            node_text(&parent, "artifactId")?,
+            node_text(&parent, "groupId"),
+            node_text(&parent, "version")?,
+            PackageKind::Pom,
+        ))
+    }
+
     fn parse_deps(&self) -> Vec<Artifact> {
         match node(self, "dependencies") {
             Some(dependencies) => dependencies
@@ -202,7 +403,7 @@ fn node_text<'a, 'input: 'a>(parent: &'input Node, tag_name: &'a str) -> Option<
 
 #[cfg(test)]
 mod tests {
-    use crate::{parser::maven::MavenParser, Parser};
+    use crate::{parser::maven::MavenParser, PackageKind, Parser};
 
     #[test]
     fn transitive_pkgs() {
@@ -211,7 +412,7 @@ mod tests {
             .join(".buildk/cache")
             .join("org/jetbrains/kotlin/kotlin-stdlib/1.9.22")
             .join("kotlin-stdlib-1.9.22.pom");
-        let pkgs = MavenParser::parse(pom);
+        let pkgs = MavenParser::parse(pom, PackageKind::Compile, None);
 
         pkgs.iter().for_each(|pkg| {
             println!("name: {}", pkg.name);
@@ -219,4 +420,69 @@ mod tests {
             println!("version: {}", pkg.version);
         });
     }
+
+    #[test]
+    fn managed_deps_only_contribute_versions() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let pom = dir.join("maven.xml");
+        std::fs::write(
+            &pom,
+            r#"<project>
+                <dependencyManagement>
+                    <dependencies>
+                        <dependency><groupId>io.ktor</groupId><artifactId>ktor-http</artifactId><version>2.3.11</version></dependency>
+                        <dependency><groupId>io.ktor</groupId><artifactId>ktor-utils</artifactId><version>2.3.11</version></dependency>
+                    </dependencies>
+                </dependencyManagement>
+                <dependencies>
+                    <dependency><groupId>io.ktor</groupId><artifactId>ktor-http</artifactId></dependency>
+                </dependencies>
+            </project>"#,
+        )
+        .unwrap();
+
+        let pkgs = MavenParser::parse(pom, PackageKind::Compile, None);
+        let pkgs = pkgs.iter().map(|pkg| format!("{}:{}", pkg.name, pkg.version)).collect::<Vec<_>>();
+        assert_eq!(pkgs, ["ktor-http:2.3.11"]);
+
+        let managed = super::managed(dir, None);
+        assert_eq!(managed.len(), 2);
+    }
+
+    #[test]
+    fn inherits_versions_from_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        let pom = dir.path().join("maven.xml");
+        std::fs::write(
+            &pom,
+            r#"<project>
+                <parent>
+                    <groupId>com.fasterxml.jackson</groupId>
+                    <artifactId>jackson-base</artifactId>
+                    <version>2.17.1</version>
+                </parent>
+                <artifactId>jackson-databind</artifactId>
+                <dependencies>
+                    <dependency><groupId>${project.groupId}.core</groupId><artifactId>jackson-core</artifactId><version>${project.version}</version></dependency>
+                    <dependency><groupId>com.fasterxml.jackson.core</groupId><artifactId>jackson-annotations</artifactId><version>${project.parent.version}</version></dependency>
+                </dependencies>
+            </project>"#,
+        )
+        .unwrap();
+
+        let pkgs = MavenParser::parse(pom, PackageKind::Compile, None);
+        let pkgs = pkgs
+            .iter()
+            .map(|pkg| format!("{}:{}:{}", pkg.namespace.clone().unwrap(), pkg.name, pkg.version))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pkgs,
+            [
+                "com.fasterxml.jackson.core:jackson-annotations:2.17.1",
+                "com.fasterxml.jackson:jackson-base:2.17.1",
+                "com.fasterxml.jackson.core:jackson-core:2.17.1",
+            ]
+        );
+    }
 }
@@ -1,20 +1,21 @@
 use std::{collections::BTreeSet, path::Path};
 
+use crate::local::{self, CachedFile};
 use crate::{Package, PackageKind, Parser};
 
 pub mod buildk;
 pub mod gradle;
 pub mod maven;
 
-pub fn parse(path: &Path, kind: PackageKind) -> BTreeSet<Package> {
-    let descriptor = path.join("maven.xml");
+pub fn parse(path: &Path, kind: PackageKind, maven_local: Option<&Path>) -> BTreeSet<Package> {
+    let descriptor = local::file(path, CachedFile::Pom, maven_local);
     if descriptor.exists() {
-        return maven::MavenParser::parse(descriptor, kind);
+        return maven::MavenParser::parse(descriptor, kind, maven_local);
     }
 
-    let descriptor = path.join("gradle.json");
+    let descriptor = local::file(path, CachedFile::Module, maven_local);
     if descriptor.exists() {
-        return gradle::GradleParser::parse(descriptor, kind);
+        return gradle::GradleParser::parse(descriptor, kind, maven_local);
     }
 
     return BTreeSet::new();
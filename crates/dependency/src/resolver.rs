@@ -0,0 +1,443 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::str::FromStr;
+
+use crate::{version, Exclusion, Package, PackageKind};
+
+/// How to pick one version when a module is requested in several versions.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Strategy {
+    /// Maven: the version closest to the manifest wins, first declaration breaks ties.
+    #[default]
+    Nearest,
+    /// Gradle: the highest requested version wins.
+    Highest,
+}
+
+impl FromStr for Strategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "nearest" => Strategy::Nearest,
+            "highest" => Strategy::Highest,
+            _ => anyhow::bail!("Invalid resolution strategy: {} (expected nearest or highest)", s),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    pub rejected: Package,
+    pub selected: Package,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct Resolution {
+    /// One package per module, in the order they were first encountered.
+    pub selected: Vec<Package>,
+    pub conflicts: Vec<Conflict>,
+}
+
+type Module = (Option<String>, String);
+
+fn module(pkg: &Package) -> Module {
+    (pkg.namespace.clone(), pkg.name.clone())
+}
+
+/// Resolve the transitive graph of `roots` to a single version per module.
+pub fn resolve(roots: &[Package], strategy: Strategy) -> Resolution {
+    resolve_with(roots, strategy, Package::transitives)
+}
+
+/// Like [resolve], but a module in `pins` always resolves to the pinned version.
+pub fn resolve_pinned(roots: &[Package], strategy: Strategy, pins: &[Package]) -> Resolution {
+    resolve_inner(roots, strategy, pins, Package::transitives)
+}
+
+pub fn resolve_with<F>(roots: &[Package], strategy: Strategy, transitives: F) -> Resolution
+where
+    F: Fn(&Package) -> Vec<Package>,
+{
+    resolve_inner(roots, strategy, &[], transitives)
+}
+
+fn resolve_inner<F>(roots: &[Package], strategy: Strategy, pins: &[Package], transitives: F) -> Resolution
+where
+    F: Fn(&Package) -> Vec<Package>,
+{
+    let mut winners = BTreeMap::new();
+    let mut encountered = walk(roots, &winners, &transitives);
+
+    // the graph changes when a different version wins, walk it again until the selection is stable
+    for _ in 0..16 {
+        let next = select(&encountered, strategy, pins);
+        if next == winners {
+            break;
+        }
+        winners = next;
+        encountered = walk(roots, &winners, &transitives);
+    }
+
+    let mut selected = vec![];
+    let mut conflicts = vec![];
+    let mut seen = BTreeSet::new();
+
+    for (pkg, _) in encountered.iter() {
+        // every child needs its own parent, poms are not unified
+        if pkg.kind == PackageKind::Pom {
+            if !selected.contains(pkg) {
+                selected.push(pkg.clone());
+            }
+            continue;
+        }
+
+        let winner = winners.get(&module(pkg)).unwrap_or(pkg);
+        if seen.insert(module(pkg)) {
+            selected.push(winner.clone());
+        }
+        if pkg.version != winner.version {
+            let conflict = Conflict { rejected: pkg.clone(), selected: winner.clone() };
+            if !conflicts.contains(&conflict) {
+                conflicts.push(conflict);
+            }
+        }
+    }
+
+    Resolution { selected, conflicts }
+}
+
+/// Breadth first walk, every requested package is recorded with its depth.
+/// Edges to a module with a selected version are followed to that version instead.
+/// Exclusions apply to everything below the package declaring them, on that path only:
+/// a package reached with other exclusions is expanded again.
+fn walk<F>(roots: &[Package], winners: &BTreeMap<Module, Package>, transitives: &F) -> Vec<(Package, usize)>
+where
+    F: Fn(&Package) -> Vec<Package>,
+{
+    let mut queue = roots.iter().cloned().map(|pkg| (pkg, 0, BTreeSet::new())).collect::<VecDeque<_>>();
+    let mut visited = BTreeSet::new();
+    let mut encountered = vec![];
+
+    while let Some((pkg, depth, mut excluded)) = queue.pop_front() {
+        let next = winners.get(&module(&pkg)).cloned().unwrap_or(pkg.clone());
+        excluded.extend(pkg.exclusions.iter().chain(next.exclusions.iter()).cloned());
+        encountered.push((pkg, depth));
+
+        if visited.insert((next.clone(), excluded.clone())) {
+            transitives(&next)
+                .into_iter()
+                .filter(|transitive| !excluded.iter().any(|exclusion| exclusion.matches(transitive)))
+                .for_each(|transitive| queue.push_back((transitive, depth + 1, excluded.clone())));
+        }
+    }
+
+    encountered
+}
+
+fn select(encountered: &[(Package, usize)], strategy: Strategy, pins: &[Package]) -> BTreeMap<Module, Package> {
+    let mut winners: BTreeMap<Module, (Package, usize)> = BTreeMap::new();
+
+    for (pkg, depth) in encountered.iter().filter(|(pkg, _)| pkg.kind != PackageKind::Pom) {
+        match winners.get(&module(pkg)) {
+            None => {
+                winners.insert(module(pkg), (pkg.clone(), *depth));
+            }
+            Some((winner, winner_depth)) => {
+                let replace = match strategy {
+                    Strategy::Nearest => depth < winner_depth,
+                    Strategy::Highest => version::compare(&pkg.version, &winner.version) == Ordering::Greater,
+                };
+                if replace {
+                    winners.insert(module(pkg), (pkg.clone(), *depth));
+                }
+            }
+        }
+    }
+
+    winners
+        .into_iter()
+        .map(|(key, (pkg, _))| match pins.iter().find(|pin| module(pin) == key) {
+            Some(pin) => {
+                let pinned = Package::new(pkg.name.clone(), pkg.namespace.clone(), pin.version.clone(), pkg.kind.clone())
+                    .exclude(pkg.exclusions.clone())
+                    .maven_local(pkg.maven_local.clone());
+                (key, pinned)
+            }
+            None => (key, pkg),
+        })
+        .collect()
+}
+
+/// The packages each selected package requests, in the versions requested, for the graph resolved to `selected`.
+/// Poms are left out, they only hold versions.
+/// Exclusions apply along the path as in [walk], a request is kept when it isn't excluded on some path to the package.
+pub fn edges<F>(roots: &[Package], selected: &[Package], transitives: F) -> BTreeMap<Package, Vec<Package>>
+where
+    F: Fn(&Package) -> Vec<Package>,
+{
+    let winner = winner(selected);
+
+    let mut edges: BTreeMap<Package, Vec<Package>> = BTreeMap::new();
+    let mut visited = BTreeSet::new();
+    let mut queue = roots.iter().cloned().map(|pkg| (pkg, BTreeSet::new())).collect::<VecDeque<_>>();
+    while let Some((requested, mut excluded)) = queue.pop_front() {
+        let pkg = winner(&requested);
+        excluded.extend(requested.exclusions.iter().chain(pkg.exclusions.iter()).cloned());
+        if !visited.insert((pkg.clone(), excluded.clone())) {
+            continue;
+        }
+
+        let children = transitives(&pkg)
+            .into_iter()
+            .filter(|it| it.kind != PackageKind::Pom)
+            .filter(|it| !excluded.iter().any(|exclusion| exclusion.matches(it)))
+            .collect::<Vec<_>>();
+        queue.extend(children.iter().map(|child| (child.clone(), excluded.clone())));
+
+        let requests = edges.entry(pkg).or_default();
+        for child in children {
+            if !requests.contains(&child) {
+                requests.push(child);
+            }
+        }
+    }
+    edges
+}
+
+/// The selected version of a requested package.
+pub fn winner(selected: &[Package]) -> impl Fn(&Package) -> Package {
+    let winners = selected
+        .iter()
+        .filter(|pkg| pkg.kind != PackageKind::Pom)
+        .map(|pkg| (module(pkg), pkg.clone()))
+        .collect::<BTreeMap<_, _>>();
+
+    move |pkg: &Package| winners.get(&module(pkg)).cloned().unwrap_or(pkg.clone())
+}
+
+/// Every path from a root down to a package matching `target`, through the graph resolved to `selected`.
+/// A path ends at the first match, and holds the packages as requested, e.g. `lib:2.0` where `lib:1.0` is selected.
+/// Like Gradle's `dependencyInsight`, the paths below a package are only listed the first time it is reached,
+/// a later path ends at it without matching `target`. There are as many paths as edges, not one per combination.
+pub fn paths<F, T>(roots: &[Package], selected: &[Package], target: T, transitives: F) -> Vec<Vec<Package>>
+where
+    F: Fn(&Package) -> Vec<Package>,
+    T: Fn(&Package) -> bool,
+{
+    let winner = winner(selected);
+    let edges = edges(roots, selected, transitives);
+
+    // only packages leading to the target are worth following
+    let mut leading = BTreeSet::new();
+    loop {
+        let before = leading.len();
+        for (pkg, children) in edges.iter() {
+            if target(pkg) || children.iter().any(|child| target(child) || leading.contains(&winner(child))) {
+                leading.insert(pkg.clone());
+            }
+        }
+        if leading.len() == before {
+            break;
+        }
+    }
+
+    let mut paths = vec![];
+    let mut expanded = BTreeSet::new();
+    let graph = Graph { edges: &edges, leading: &leading, winner: &winner, target: &target };
+    for root in roots {
+        descend(&mut vec![root.clone()], &[], &mut expanded, &mut paths, &graph);
+    }
+    paths
+}
+
+struct Graph<'a, W, T> {
+    edges: &'a BTreeMap<Package, Vec<Package>>,
+    leading: &'a BTreeSet<Package>,
+    winner: &'a W,
+    target: &'a T,
+}
+
+/// `expanded` holds the packages whose paths were listed, with the exclusions on the way to them.
+fn descend<W, T>(
+    path: &mut Vec<Package>,
+    excluded: &[Exclusion],
+    expanded: &mut BTreeSet<(Package, BTreeSet<Exclusion>)>,
+    paths: &mut Vec<Vec<Package>>,
+    graph: &Graph<W, T>,
+) where
+    W: Fn(&Package) -> Package,
+    T: Fn(&Package) -> bool,
+{
+    let Some(pkg) = path.last().cloned() else {
+        return;
+    };
+    if (graph.target)(&pkg) {
+        paths.push(path.clone());
+        return;
+    }
+
+    let next = (graph.winner)(&pkg);
+    let excluded = excluded.iter().chain(pkg.exclusions.iter()).chain(next.exclusions.iter()).cloned().collect::<Vec<_>>();
+    if !expanded.insert((next.clone(), excluded.iter().cloned().collect())) {
+        paths.push(path.clone());
+        return;
+    }
+
+    for child in graph.edges.get(&next).into_iter().flatten() {
+        let leads = (graph.target)(child) || graph.leading.contains(&(graph.winner)(child));
+        let cycle = path.iter().any(|it| module(it) == module(child));
+        if leads && !cycle && !excluded.iter().any(|exclusion| exclusion.matches(child)) {
+            path.push(child.clone());
+            descend(path, &excluded, expanded, paths, graph);
+            path.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Exclusion, Package, PackageKind};
+
+    use super::{edges, paths, resolve_inner, resolve_with, Strategy};
+
+    fn pkg(name: &str, version: &str) -> Package {
+        Package::new(name.into(), Some("org.example".into()), version.into(), PackageKind::Compile)
+    }
+
+    // app -> lib:1.0 -> util:1.0
+    // app -> other:1.0 -> lib:2.0 -> util:2.0
+    fn transitives(pkg: &Package) -> Vec<Package> {
+        match (pkg.name.as_str(), pkg.version.as_str()) {
+            ("lib", "1.0") => vec![self::pkg("util", "1.0")],
+            ("lib", "2.0") => vec![self::pkg("util", "2.0")],
+            ("other", _) => vec![self::pkg("lib", "2.0")],
+            _ => vec![],
+        }
+    }
+
+    fn versions(pkgs: &[Package]) -> Vec<String> {
+        pkgs.iter().map(|pkg| format!("{}:{}", pkg.name, pkg.version)).collect()
+    }
+
+    #[test]
+    fn nearest_wins() {
+        let roots = [pkg("lib", "1.0"), pkg("other", "1.0")];
+        let resolution = resolve_with(&roots, Strategy::Nearest, transitives);
+
+        assert_eq!(versions(&resolution.selected), ["lib:1.0", "other:1.0", "util:1.0"]);
+        assert_eq!(resolution.conflicts.len(), 1);
+        assert_eq!(resolution.conflicts[0].rejected.version, "2.0");
+    }
+
+    #[test]
+    fn highest_wins() {
+        let roots = [pkg("lib", "1.0"), pkg("other", "1.0")];
+        let resolution = resolve_with(&roots, Strategy::Highest, transitives);
+
+        assert_eq!(versions(&resolution.selected), ["lib:2.0", "other:1.0", "util:2.0"]);
+        assert_eq!(resolution.conflicts.len(), 1);
+        assert_eq!(resolution.conflicts[0].rejected.version, "1.0");
+    }
+
+    #[test]
+    fn excluded_below_declaring_package() {
+        let roots = [pkg("other", "1.0").exclude(vec![Exclusion::new("org.example".into(), "util".into())])];
+        let resolution = resolve_with(&roots, Strategy::Nearest, transitives);
+
+        assert_eq!(versions(&resolution.selected), ["other:1.0", "lib:2.0"]);
+    }
+
+    #[test]
+    fn excluded_on_its_path_only() {
+        // a and b both depend on other, only a excludes util below it
+        let transitives = |pkg: &Package| match pkg.name.as_str() {
+            "a" | "b" => vec![self::pkg("other", "1.0")],
+            _ => transitives(pkg),
+        };
+        let exclusion = || vec![Exclusion::new("org.example".into(), "util".into())];
+
+        let roots = [pkg("a", "1.0").exclude(exclusion()), pkg("b", "1.0")];
+        let resolution = resolve_with(&roots, Strategy::Nearest, transitives);
+        assert_eq!(versions(&resolution.selected), ["a:1.0", "b:1.0", "other:1.0", "lib:2.0", "util:2.0"]);
+
+        let roots = [pkg("a", "1.0").exclude(exclusion()), pkg("b", "1.0").exclude(exclusion())];
+        let resolution = resolve_with(&roots, Strategy::Nearest, transitives);
+        assert_eq!(versions(&resolution.selected), ["a:1.0", "b:1.0", "other:1.0", "lib:2.0"]);
+    }
+
+    #[test]
+    fn edges_excluded_on_their_path() {
+        // util is selected as a root, other excludes it below lib
+        let exclusion = vec![Exclusion::new("org.example".into(), "util".into())];
+        let roots = [pkg("other", "1.0").exclude(exclusion), pkg("util", "1.0")];
+        let resolution = resolve_with(&roots, Strategy::Nearest, transitives);
+        assert_eq!(versions(&resolution.selected), ["other:1.0", "util:1.0", "lib:2.0"]);
+
+        let graph = edges(&roots, &resolution.selected, transitives);
+        assert!(graph[&pkg("lib", "2.0")].is_empty());
+
+        let roots = [pkg("other", "1.0")];
+        let graph = edges(&roots, &resolve_with(&roots, Strategy::Nearest, transitives).selected, transitives);
+        assert_eq!(versions(&graph[&pkg("lib", "2.0")]), ["util:2.0"]);
+    }
+
+    #[test]
+    fn pinned_wins() {
+        let roots = [pkg("lib", "1.0"), pkg("other", "1.0")];
+        let pins = [pkg("util", "2.0")];
+        let resolution = resolve_inner(&roots, Strategy::Nearest, &pins, transitives);
+
+        assert_eq!(versions(&resolution.selected), ["lib:1.0", "other:1.0", "util:2.0"]);
+    }
+
+    #[test]
+    fn pinned_keeps_maven_local_and_exclusions() {
+        // a BOM pins the mavenLocal root lib to 2.0
+        let repository = std::path::PathBuf::from("/m2/repository");
+        let exclusion = vec![Exclusion::new("org.example".into(), "util".into())];
+        let roots = [pkg("lib", "1.0").exclude(exclusion.clone()).maven_local(Some(repository.clone()))];
+        let pins = [pkg("lib", "2.0")];
+        let resolution = resolve_inner(&roots, Strategy::Nearest, &pins, transitives);
+
+        assert_eq!(versions(&resolution.selected), ["lib:2.0"]);
+        assert_eq!(resolution.selected[0].maven_local, Some(repository));
+        assert_eq!(resolution.selected[0].exclusions, exclusion);
+    }
+
+    #[test]
+    fn paths_to_a_transitive() {
+        let roots = [pkg("lib", "1.0"), pkg("other", "1.0")];
+        let resolution = resolve_with(&roots, Strategy::Nearest, transitives);
+        let paths = paths(&roots, &resolution.selected, |pkg| pkg.name == "util", transitives);
+
+        // lib is selected in 1.0, its paths were listed already
+        assert_eq!(paths.iter().map(|path| versions(path)).collect::<Vec<_>>(), [
+            vec!["lib:1.0", "util:1.0"],
+            vec!["other:1.0", "lib:2.0"],
+        ]);
+    }
+
+    #[test]
+    fn paths_through_shared_packages_are_listed_once() {
+        // a0 and b0 both depend on a1 and b1, and so on, 2^20 combinations down to util
+        let levels = 20;
+        let transitives = |pkg: &Package| match pkg.name[1..].parse::<usize>() {
+            Ok(level) if level < levels => vec![pkg_at("a", level + 1), pkg_at("b", level + 1)],
+            Ok(_) => vec![self::pkg("util", "1.0")],
+            Err(_) => vec![],
+        };
+
+        let roots = [pkg_at("a", 0), pkg_at("b", 0)];
+        let resolution = resolve_with(&roots, Strategy::Nearest, transitives);
+        let paths = paths(&roots, &resolution.selected, |pkg| pkg.name == "util", transitives);
+
+        // through a20 and b20, every other edge ends at a package listed before
+        assert_eq!(paths.iter().filter(|path| path.last().unwrap().name == "util").count(), 2);
+        assert_eq!(paths.len(), 2 * levels + 2);
+    }
+
+    fn pkg_at(name: &str, level: usize) -> Package {
+        pkg(&format!("{name}{level}"), "1.0")
+    }
+}
@@ -0,0 +1,406 @@
+use std::cmp::Ordering;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Compare two versions by Maven's rules, numeric segments are compared as numbers
+/// and qualifiers are ordered alpha < beta < milestone < rc < snapshot < release < sp.
+/// Missing segments count as zero or release, so 1 == 1.0 == 1.0.0 == 1.0-ga.
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let a = items(a);
+    let b = items(b);
+
+    for i in 0..a.len().max(b.len()) {
+        let ordering = match (a.get(i), b.get(i)) {
+            (Some(x), Some(y)) => x.cmp(y),
+            (Some(x), None) => x.cmp_missing(),
+            (None, Some(y)) => y.cmp_missing().reverse(),
+            (None, None) => Ordering::Equal,
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    Ordering::Equal
+}
+
+pub fn is_snapshot(version: &str) -> bool {
+    version.to_lowercase().ends_with("-snapshot")
+}
+
+/// A range or dynamic version that must be resolved against the available versions.
+pub fn is_dynamic(version: &str) -> bool {
+    Requirement::from_str(version).is_ok_and(|it| it.is_dynamic())
+}
+
+/// Alpha, beta, milestone, rc, snapshot and other unstable qualifiers, e.g. `2.0.0-Beta1`, `1.9.0-dev-123`.
+pub fn is_pre_release(version: &str) -> bool {
+    items(version).iter().any(|item| match item {
+        Item::Qualifier(qualifier) => {
+            Item::rank(qualifier) < Item::rank("") || ["dev", "eap", "preview", "pre"].contains(&qualifier.as_str())
+        }
+        Item::Number(_) => false,
+    })
+}
+
+/// How far an update may go from the current version.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Upgrade {
+    /// `1.2.3` -> `1.2.x`
+    Patch,
+    /// `1.2.3` -> `1.x`
+    Minor,
+    /// `1.2.3` -> anything newer
+    Major,
+}
+
+impl Upgrade {
+    pub const ALL: [Upgrade; 3] = [Upgrade::Patch, Upgrade::Minor, Upgrade::Major];
+
+    /// The highest available version newer than `current` within this upgrade.
+    /// Pre-releases are skipped unless asked for.
+    pub fn select(self, current: &str, available: &[String], pre_releases: bool) -> Option<String> {
+        let fixed = match self {
+            Upgrade::Patch => 2,
+            Upgrade::Minor => 1,
+            Upgrade::Major => 0,
+        };
+
+        available
+            .iter()
+            .filter(|version| compare(version, current) == Ordering::Greater)
+            .filter(|version| pre_releases || !is_pre_release(version))
+            .filter(|version| release(version).take(fixed).eq(release(current).take(fixed)))
+            .max_by(|a, b| compare(a, b))
+            .cloned()
+    }
+}
+
+/// The numeric segments before the first qualifier, padded with zeros: 1.2 -> 1, 2, 0, 0 ...
+fn release(version: &str) -> impl Iterator<Item = String> {
+    items(version)
+        .into_iter()
+        .map_while(|item| match item {
+            Item::Number(number) => Some(number),
+            Item::Qualifier(_) => None,
+        })
+        .chain(std::iter::repeat(String::new()))
+}
+
+#[derive(PartialEq, Eq, Debug)]
+enum Item {
+    Number(String), // without leading zeros, may not fit in a u64
+    Qualifier(String),
+}
+
+impl Item {
+    fn rank(qualifier: &str) -> usize {
+        match qualifier {
+            "alpha" => 0,
+            "beta" => 1,
+            "milestone" => 2,
+            "rc" => 3,
+            "snapshot" => 4,
+            "" => 5,
+            "sp" => 6,
+            _ => 7,
+        }
+    }
+
+    fn cmp_missing(&self) -> Ordering {
+        match self {
+            Item::Number(number) if number.is_empty() => Ordering::Equal,
+            Item::Number(_) => Ordering::Greater,
+            Item::Qualifier(qualifier) => Item::rank(qualifier).cmp(&Item::rank("")),
+        }
+    }
+}
+
+impl Ord for Item {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Item::Number(x), Item::Number(y)) => x.len().cmp(&y.len()).then_with(|| x.cmp(y)),
+            (Item::Number(_), Item::Qualifier(_)) => Ordering::Greater,
+            (Item::Qualifier(_), Item::Number(_)) => Ordering::Less,
+            (Item::Qualifier(x), Item::Qualifier(y)) => Item::rank(x).cmp(&Item::rank(y)).then_with(|| x.cmp(y)),
+        }
+    }
+}
+
+impl PartialOrd for Item {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Split on `.` and `-` and where digits and letters meet, e.g. 1.0rc1 -> 1, 0, rc, 1
+fn items(version: &str) -> Vec<Item> {
+    let mut tokens: Vec<String> = vec![];
+    let mut current = String::new();
+
+    for c in version.to_lowercase().chars() {
+        let switches = current
+            .chars()
+            .last()
+            .is_some_and(|last| last.is_ascii_digit() != c.is_ascii_digit());
+
+        if c == '.' || c == '-' || switches {
+            tokens.push(std::mem::take(&mut current));
+        }
+        if c != '.' && c != '-' {
+            current.push(c);
+        }
+    }
+    tokens.push(current);
+
+    let followed_by_number = |i: usize| tokens.get(i + 1).is_some_and(|next| next.starts_with(|c: char| c.is_ascii_digit()));
+
+    tokens
+        .iter()
+        .enumerate()
+        .map(|(i, token)| match token.chars().all(|c| c.is_ascii_digit()) {
+            true => Item::Number(token.trim_start_matches('0').to_owned()),
+            false => Item::Qualifier(
+                match token.as_str() {
+                    "a" if followed_by_number(i) => "alpha",
+                    "b" if followed_by_number(i) => "beta",
+                    "m" if followed_by_number(i) => "milestone",
+                    "cr" => "rc",
+                    "ga" | "final" | "release" => "",
+                    other => other,
+                }
+                .to_owned(),
+            ),
+        })
+        .collect()
+}
+
+/// A requested version.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Requirement {
+    /// `1.0`, a preference that conflict resolution may override
+    Soft(String),
+    /// Maven ranges: `[1.0,2.0)`, `[1.5]`, `(,1.0],[1.2,)`
+    Ranges(Vec<Range>),
+    /// Gradle prefixes: `1.+`, `+`
+    Prefix(String),
+    /// `latest.release` or `RELEASE`
+    LatestRelease,
+    /// `latest.integration` or `LATEST`, snapshots included
+    LatestIntegration,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Range {
+    lower: Option<Bound>,
+    upper: Option<Bound>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Bound {
+    version: String,
+    inclusive: bool,
+}
+
+impl FromStr for Requirement {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Ok(match s {
+            "latest.release" | "RELEASE" => Requirement::LatestRelease,
+            "latest.integration" | "LATEST" => Requirement::LatestIntegration,
+            "+" => Requirement::Prefix(String::new()),
+            _ if s.ends_with(".+") => Requirement::Prefix(s.trim_end_matches('+').to_owned()),
+            _ if s.starts_with(['[', '(']) => Requirement::Ranges(ranges(s)?),
+            _ => Requirement::Soft(s.to_owned()),
+        })
+    }
+}
+
+fn ranges(s: &str) -> anyhow::Result<Vec<Range>> {
+    let mut ranges = vec![];
+    let mut rest = s;
+
+    while !rest.is_empty() {
+        let end = rest
+            .find([']', ')'])
+            .ok_or_else(|| anyhow::anyhow!("Unclosed version range: {}", s))?;
+        let (range, tail) = rest.split_at(end + 1);
+        ranges.push(range.parse()?);
+        rest = tail.trim_start_matches([',', ' ']);
+    }
+
+    Ok(ranges)
+}
+
+impl FromStr for Range {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() < 2 || !s.starts_with(['[', '(']) || !s.ends_with([']', ')']) {
+            anyhow::bail!("Invalid version range: {}", s);
+        }
+
+        let lower_inclusive = s.starts_with('[');
+        let upper_inclusive = s.ends_with(']');
+        let inner = &s[1..s.len() - 1];
+
+        let bound = |version: &str, inclusive: bool| {
+            let version = version.trim();
+            (!version.is_empty()).then(|| Bound { version: version.to_owned(), inclusive })
+        };
+
+        Ok(match inner.split_once(',') {
+            Some((lower, upper)) => Range {
+                lower: bound(lower, lower_inclusive),
+                upper: bound(upper, upper_inclusive),
+            },
+            None if lower_inclusive && upper_inclusive => Range {
+                lower: bound(inner, true),
+                upper: bound(inner, true),
+            },
+            None => anyhow::bail!("Invalid version range: {}", s),
+        })
+    }
+}
+
+impl Range {
+    fn contains(&self, version: &str) -> bool {
+        let above = match &self.lower {
+            None => true,
+            Some(bound) => match compare(version, &bound.version) {
+                Ordering::Greater => true,
+                Ordering::Equal => bound.inclusive,
+                Ordering::Less => false,
+            },
+        };
+        let below = match &self.upper {
+            None => true,
+            Some(bound) => match compare(version, &bound.version) {
+                Ordering::Less => true,
+                Ordering::Equal => bound.inclusive,
+                Ordering::Greater => false,
+            },
+        };
+        above && below
+    }
+}
+
+impl Requirement {
+    pub fn is_dynamic(&self) -> bool {
+        !matches!(self, Requirement::Soft(_))
+    }
+
+    pub fn matches(&self, version: &str) -> bool {
+        match self {
+            Requirement::Soft(soft) => compare(soft, version) == Ordering::Equal,
+            Requirement::Ranges(ranges) => ranges.iter().any(|range| range.contains(version)),
+            Requirement::Prefix(prefix) => version.starts_with(prefix.as_str()),
+            Requirement::LatestRelease => !is_snapshot(version),
+            Requirement::LatestIntegration => true,
+        }
+    }
+
+    /// The highest available version that matches, snapshots only for `latest.integration`.
+    pub fn select(&self, available: &[String]) -> Option<String> {
+        available
+            .iter()
+            .filter(|version| self.matches(version))
+            .filter(|version| *self == Requirement::LatestIntegration || !is_snapshot(version))
+            .max_by(|a, b| compare(a, b))
+            .cloned()
+    }
+}
+
+/// Versions listed in the `maven-metadata*.xml` files downloaded to a module directory,
+/// and in the local Maven repository `maven_local`.
+pub fn available(module: &Path, maven_local: Option<&Path>) -> Vec<String> {
+    let mut versions = [Some(module.to_path_buf()), maven_local.and_then(|it| crate::local::dir(module, it))]
+        .into_iter()
+        .flatten()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.starts_with("maven-metadata") && name.ends_with(".xml")
+        })
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|content| metadata_versions(&content))
+        .collect::<Vec<_>>();
+
+    versions.sort_by(|a, b| compare(a, b));
+    versions.dedup();
+    versions
+}
+
+fn metadata_versions(content: &str) -> Vec<String> {
+    let Ok(doc) = roxmltree::Document::parse(content) else {
+        return vec![];
+    };
+
+    doc.descendants()
+        .filter(|node| node.has_tag_name("version") && node.parent().is_some_and(|it| it.has_tag_name("versions")))
+        .filter_map(|node| node.text())
+        .map(|version| version.trim().to_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use super::{compare, is_pre_release, Requirement, Upgrade};
+
+    #[test]
+    fn maven_ordering() {
+        let ordered = [
+            "1.0-alpha1",
+            "1.0-beta-2",
+            "1.0-M3",
+            "1.0-rc1",
+            "1.0-SNAPSHOT",
+            "1.0",
+            "1.0-sp1",
+            "1.0.1",
+            "1.10",
+            "2",
+        ];
+        for pair in ordered.windows(2) {
+            assert_eq!(compare(pair[0], pair[1]), Ordering::Less, "{} < {}", pair[0], pair[1]);
+        }
+        assert_eq!(compare("1", "1.0.0-ga"), Ordering::Equal);
+    }
+
+    #[test]
+    fn selects_from_ranges() {
+        let available = ["1.0", "1.5", "2.0-SNAPSHOT", "2.0", "3.1"].map(String::from);
+        let select = |req: &str| req.parse::<Requirement>().unwrap().select(&available);
+
+        assert_eq!(select("[1.0,2.0)"), Some("1.5".into()));
+        assert_eq!(select("[1.0,2.0]"), Some("2.0".into()));
+        assert_eq!(select("(,1.0],[3.0,)"), Some("3.1".into()));
+        assert_eq!(select("[1.5]"), Some("1.5".into()));
+        assert_eq!(select("1.+"), Some("1.5".into()));
+        assert_eq!(select("latest.release"), Some("3.1".into()));
+        assert_eq!(select("[4.0,)"), None);
+        assert!(!"1.0".parse::<Requirement>().unwrap().is_dynamic());
+
+        assert!("[1.0,2.0)]".parse::<Requirement>().is_err());
+        assert!("[1.0],x]".parse::<Requirement>().is_err());
+    }
+
+    #[test]
+    fn selects_upgrades() {
+        let available = ["1.2.3", "1.2.5", "1.3.0", "1.4.0-RC1", "2.0.0", "2.1.0-beta", "3.0-SNAPSHOT"].map(String::from);
+
+        assert_eq!(Upgrade::Patch.select("1.2.3", &available, false), Some("1.2.5".into()));
+        assert_eq!(Upgrade::Minor.select("1.2.3", &available, false), Some("1.3.0".into()));
+        assert_eq!(Upgrade::Minor.select("1.2.3", &available, true), Some("1.4.0-RC1".into()));
+        assert_eq!(Upgrade::Major.select("1.2.3", &available, false), Some("2.0.0".into()));
+        assert_eq!(Upgrade::Patch.select("2.0", &available, false), None);
+        assert!(!is_pre_release("33.0.0-jre"));
+    }
+}
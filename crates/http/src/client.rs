@@ -1,12 +1,26 @@
-use anyhow::anyhow;
-use async_std::{fs::{create_dir_all, File, remove_file}, io, path::{Path, PathBuf}, task};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use async_std::{fs::{create_dir_all, rename, write, OpenOptions, remove_file}, io::{self, WriteExt}, path::{Path, PathBuf}};
 
 use dependency::Package;
-use manifest::{config::BuildK, repos::Repo};
+use manifest::{config::BuildK, credentials::Credentials, repos::Repo, Manifest};
+use surf::http::auth::{AuthenticationScheme, Authorization, BasicAuth};
+use surf::StatusCode;
+use util::colorize::Colorize;
+use util::hasher::Checksum;
 use util::DEBUG;
 
+use crate::retry::{Failure, Policy};
+
+/// Downloads share one http client, so connections to a repository are kept alive and reused.
 #[derive(Default, Clone)]
-pub struct Client;
+pub struct Client {
+    http: surf::Client,
+    bytes: Arc<AtomicU64>,
+    retry: Policy,
+}
 
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum DownloadResult {
@@ -16,30 +30,64 @@ pub enum DownloadResult {
 }
 
 impl Client {
+    pub fn new(retry: Policy) -> Client {
+        Client { retry, ..Client::default() }
+    }
+
+    /// Bytes downloaded by this client and its clones.
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
     pub async fn download_async<'a>(&'a self, pkg: &'a Package, buildk: &'a BuildK) -> DownloadResult {
+        if buildk.offline {
+            return DownloadResult::Failed(format!("{} is not in the cache, offline", pkg.coordinates()));
+        }
+
+        // TODO: support no namespace, repos lay packages out under it
+        if pkg.namespace.is_none() {
+            return DownloadResult::Failed(format!("{} has no namespace, it cannot be downloaded", pkg.name));
+        }
+
+        let Some(manifest) = &buildk.manifest else {
+            return DownloadResult::Failed("no buildk.toml found.".to_string());
+        };
+
         if let Err(err) = create_dir_all(&pkg.location).await {
             return DownloadResult::Failed(err.to_string());
         }
 
-        let (jar, pom) = task::block_on(async {
-            let mut jar = DownloadResult::Failed("".into());
-            let mut pom = DownloadResult::Failed("".into());
+        if pkg.is_dynamic() {
+            return self.download_metadata(pkg, manifest).await;
+        }
 
-            // FIXME
-            let manifest = <Option<manifest::Manifest> as Clone>::clone(&buildk.manifest)
-                .expect("no buildk.toml found.");
+        let (jar, pom) = async {
+            let no_repo = DownloadResult::Failed(format!("no repo may serve {}, see include and exclude in [repos]", pkg.coordinates()));
+            let mut jar = no_repo.clone();
+            let mut pom = no_repo;
 
-            for repo in manifest.repos.repos.iter() {
-                let repo = repo.clone();
-                (jar, pom) = Self::download_jar_and_pom(&pkg, &repo).await;
+            // a local repo is read in place, the package is not there when it has to be downloaded
+            for repo in manifest.repos.for_package(pkg).into_iter().filter(|repo| repo.local_path().is_none()) {
+                (jar, pom) = self.download_jar_and_pom(&pkg, repo).await;
+                if jar.is_failed() && !pom.is_failed() && pkg.is_bom() {
+                    jar = pom.clone(); // boms are published without a jar
+                }
                 if !jar.is_failed() && !pom.is_failed() {
+                    if jar.is_downloaded() || pom.is_downloaded() {
+                        let _ = write(pkg.location.join("repository"), &repo.name).await;
+                        let local = manifest.repos.local().filter(|local| local.write);
+                        if let Some(repository) = local.and_then(|local| local.local_path()) {
+                            let _ = dependency::local::install(&pkg.location, &repository);
+                        }
+                    }
                     break;
                 } else {
                     println!("\rfrom {} failed, trying", &repo.url);
                 }
             }
             (jar, pom)
-        });
+        }
+        .await;
 
         if let DownloadResult::Failed(_) = jar {
             return jar;
@@ -57,16 +105,44 @@ impl Client {
         DownloadResult::Exist
     }
 
+    /// Versions of a range or dynamic version are listed in the maven-metadata.xml of each repo.
+    async fn download_metadata(&self, pkg: &Package, manifest: &Manifest) -> DownloadResult {
+        let mut result = DownloadResult::Failed(format!("no maven-metadata.xml found for {}", pkg.name));
+
+        for repo in manifest.repos.for_package(pkg).into_iter().filter(|repo| repo.local_path().is_none()) {
+            let path = pkg.namespace.clone().unwrap_or_default().replace('.', "/");
+            let url = format!("{}/{}/{}/maven-metadata.xml", &repo.url, path, &pkg.name);
+            let target = PathBuf::from(pkg.location.join(format!("maven-metadata-{}.xml", &repo.name)));
+
+            if metadata_is_fresh(&target) {
+                result = DownloadResult::Exist;
+                continue;
+            }
+
+            let _ = remove_file(&target).await;
+            match self.create_target_and_download(&url, &target, repo.credentials.as_ref()).await {
+                DownloadResult::Failed(_) if !result.is_failed() => {}
+                res => result = res,
+            }
+        }
+
+        if !result.is_failed() && pkg.clone().resolve_version().is_dynamic() {
+            return DownloadResult::Failed(format!("no version of {} matches {}", pkg.name, pkg.version));
+        }
+
+        result
+    }
+
     fn resolve_url(pkg: &&Package, repo: &Repo) -> String {
         let name = &pkg.name;
         let version = &pkg.version;
-        let path = pkg.namespace.clone().unwrap().replace('.', "/"); // TODO: support no namespace
+        let path = pkg.namespace.clone().unwrap_or_default().replace('.', "/");
         let path = format!("{path}/{name}/{version}");
         let file_prefix = format!("{name}-{version}");
         format!("{}/{}/{}", &repo.url, &path, &file_prefix)
     }
 
-    async fn download_jar_and_pom(pkg: &&Package, repo: &Repo) -> (DownloadResult, DownloadResult) {
+    async fn download_jar_and_pom(&self, pkg: &&Package, repo: &Repo) -> (DownloadResult, DownloadResult) {
         let url = Self::resolve_url(&pkg, &repo);
         let target_dir = PathBuf::from(&pkg.location);
         let maven = target_dir.join("maven.xml");
@@ -74,10 +150,12 @@ impl Client {
         let sources = target_dir.join("sources.jar");
         let jar = target_dir.join("pkg").with_extension("jar");
 
-        let jar_res = create_target_and_download(&format!{"{}.jar", &url}, &jar).await;
-        let pom_res = create_target_and_download(&format!{"{}.pom", &url}, &maven).await;
-        let _optional = create_target_and_download(&format!{"{}-sources.jar", &url}, &sources).await;
-        let _optional = create_target_and_download(&format!{"{}.module", &url}, &gradle).await;
+        let auth = repo.credentials.as_ref();
+
+        let jar_res = self.create_target_and_download(&format!{"{}.jar", &url}, &jar, auth).await;
+        let pom_res = self.create_target_and_download(&format!{"{}.pom", &url}, &maven, auth).await;
+        let _optional = self.create_target_and_download(&format!{"{}-sources.jar", &url}, &sources, auth).await;
+        let _optional = self.create_target_and_download(&format!{"{}.module", &url}, &gradle, auth).await;
 
         if !jar_res.is_failed() && !pom_res.is_failed() {
             return (jar_res, pom_res);
@@ -101,45 +179,162 @@ impl Client {
 
         (jar_res, pom_res)
     }
-}
 
-async fn create_target_and_download(url: &String, target: &Path) -> DownloadResult {
-    if target_exists(&target).await {
-        if DEBUG {
-            println!("{} already exists", target.display());
+    async fn create_target_and_download(&self, url: &String, target: &Path, auth: Option<&Credentials>) -> DownloadResult {
+        if target_exists(&target).await {
+            if DEBUG {
+                println!("{} already exists", target.display());
+            }
+            return DownloadResult::Exist;
+        }
+
+        match self.download(url, target, auth).await {
+            Ok(_) => match self.verify_checksum(url, target, auth).await {
+                Ok(_) => DownloadResult::Downloaded,
+                Err(e) => {
+                    let refused = format!("Refused {} with err: {}", &url, e);
+                    match delete_target_file(target).await {
+                        Ok(_) => DownloadResult::Failed(refused),
+                        Err(err) => DownloadResult::Failed(format!("{refused}, and failed to delete {}: {err}", target.display())),
+                    }
+                }
+            },
+            Err(e) => DownloadResult::Failed(format!("Failed to download file from {} with err: {}", &url, e)),
         }
-        return DownloadResult::Exist;
     }
 
-    let target_file = match create_target_file(target).await {
-        Ok(file) => file,
-        Err(e) => {
-            if DEBUG {
-                println!("failed to create target file: {}", e);
+    /// Verify the downloaded file against the `.sha256` or `.sha1` the repository publishes next to it.
+    /// The verified checksum is kept next to the file, for `buildk fetch --verify`.
+    /// Without a published checksum it is accepted with a warning, and recorded as unverified.
+    /// Only a 404 means the checksum is not published, any other failure to fetch it refuses the file.
+    async fn verify_checksum(&self, url: &str, target: &Path, auth: Option<&Credentials>) -> anyhow::Result<()> {
+        let path: &std::path::Path = target.as_ref();
+
+        for checksum in Checksum::ALL {
+            let sidecar = format!("{}.{}", url, checksum.extension());
+            let published = self.download_string(&sidecar, auth).await.with_context(|| format!("failed to download {sidecar}"))?;
+            let Some(published) = published else {
+                continue;
+            };
+
+            if !checksum.matches(path, &published)? {
+                anyhow::bail!("{} checksum mismatch, expected {}", checksum.extension(), published.trim());
+            }
+
+            write(checksum.sidecar(path), checksum.digest(path)?).await?;
+            return Ok(());
+        }
+
+        eprintln!("\r{}", format!("no checksum published for {url}, not verified").as_yellow());
+        write(Checksum::unverified(path), Checksum::Sha256.digest(path)?).await?;
+        Ok(())
+    }
+
+    fn get(&self, url: &str, auth: Option<&Credentials>) -> surf::RequestBuilder {
+        let request = self.http.get(url);
+        match auth {
+            None => request,
+            Some(Credentials::Basic { username, password }) => {
+                let auth = BasicAuth::new(username, password);
+                request.header(auth.name(), auth.value())
+            }
+            Some(Credentials::Bearer(token)) => {
+                let auth = Authorization::new(AuthenticationScheme::Bearer, token.clone());
+                request.header(auth.name(), auth.value())
             }
-            return DownloadResult::Failed(format!("Failed to create target file: {}", e));
         }
-    };
+    }
+
+    /// The body of `url`, `None` when the repository does not have it.
+    async fn download_string(&self, url: &str, auth: Option<&Credentials>) -> anyhow::Result<Option<String>> {
+        self.retry
+            .run(|| async {
+                let mut response = self.get(url, auth).await.map_err(|e| Failure::retry(anyhow!(e)))?;
+                if response.status() == StatusCode::NotFound {
+                    return Ok(None);
+                }
+                if !response.status().is_success() {
+                    return Err(Failure::status(&response));
+                }
+                response.body_string().await.map(Some).map_err(|e| Failure::retry(anyhow!(e)))
+            })
+            .await
+    }
 
-    match download(&target_file, &url).await {
-        Ok(_) => DownloadResult::Downloaded,
-        Err(e) => {
-            // if DEBUG {
-            //     println!("failed to downalod file: {}", e);
-            // }
-            delete_target_file(target).await.unwrap();
-            DownloadResult::Failed(format!("Failed to download file from {} with err: {}", &url, e))
+    /// Download to `<target>.part` first, an interrupted download is resumed from there.
+    async fn download(&self, url: &str, target: &Path, auth: Option<&Credentials>) -> anyhow::Result<()> {
+        if DEBUG {
+            println!("downloading {}", url);
         }
+        let part = partial(target);
+        self.retry.run(|| self.download_part(url, &part, auth)).await?;
+        rename(&part, target).await?;
+        Ok(())
+    }
+
+    async fn download_part(&self, url: &str, part: &Path, auth: Option<&Credentials>) -> Result<(), Failure> {
+        let offset = match part.metadata().await {
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        };
+
+        let mut request = self.get(url, auth);
+        if offset > 0 {
+            request = request.header("Range", format!("bytes={offset}-"));
+        }
+        let mut response = request.await.map_err(|e| Failure::retry(anyhow!(e)))?;
+
+        let resume = match response.status() {
+            StatusCode::PartialContent => true,
+            StatusCode::RequestedRangeNotSatisfiable => {
+                let _ = remove_file(part).await; // start over
+                return Err(Failure::retry(anyhow!("cannot resume {}", url)));
+            }
+            status if status.is_success() => false,
+            _ => return Err(Failure::status(&response)),
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resume)
+            .truncate(!resume)
+            .open(part)
+            .await
+            .map_err(Failure::fatal)?;
+
+        let copied = io::copy(&mut response, &mut file).await;
+        file.flush().await.map_err(Failure::fatal)?; // the checksum is verified right after
+
+        // count what arrived, also when the connection dropped half way
+        if let Ok(metadata) = part.metadata().await {
+            let start = if resume { offset } else { 0 };
+            self.bytes.fetch_add(metadata.len().saturating_sub(start), Ordering::Relaxed);
+        }
+
+        copied.map(|_| ()).map_err(Failure::retry)
     }
 }
 
-async fn target_exists(file: &Path) -> bool {
-    file.exists().await && file.metadata().await.unwrap().len() > 0
+fn partial(target: &Path) -> PathBuf {
+    let mut part = target.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
 }
 
-async fn create_target_file(file: &Path) -> anyhow::Result<File> {
-    let file = File::create(file).await?;
-    Ok(file)
+/// Like Maven, the available versions are checked again once a day.
+fn metadata_is_fresh(target: &Path) -> bool {
+    let target: &std::path::Path = target.as_ref();
+    target
+        .metadata()
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age.as_secs() < 24 * 60 * 60)
+}
+
+async fn target_exists(file: &Path) -> bool {
+    file.exists().await && file.metadata().await.unwrap().len() > 0
 }
 
 async fn delete_target_file(file: &Path) -> anyhow::Result<()> {
@@ -150,23 +345,6 @@ async fn delete_target_file(file: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn download(mut file: &File, url: &str) -> anyhow::Result<()> {
-    if DEBUG {
-        println!("downloading {}", url);
-    }
-    let mut response = surf::get(url).await.map_err(|e| anyhow::anyhow!(e))?;
-    /* if DEBUG {
-        println!("downloaded {:?}!", response);
-    } */
-
-    if response.status().is_success() {
-        io::copy(&mut response, &mut file).await?;
-        Ok(())
-    } else {
-        Err(anyhow!(response.status().to_string()))
-    }
-}
-
 impl DownloadResult {
     pub fn is_downloaded(&self) -> bool {
         self == &DownloadResult::Downloaded
@@ -181,3 +359,144 @@ impl DownloadResult {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use async_std::task;
+    use manifest::credentials::Credentials;
+    use util::hasher::Checksum;
+
+    use super::Client;
+    use crate::retry::Policy;
+
+    /// A stand-in repository answering each connection with the next scripted response.
+    fn serve(responses: Vec<&'static str>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let seen = requests.clone();
+
+        std::thread::spawn(move || {
+            let mut responses = responses.into_iter();
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request = vec![];
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                seen.lock().unwrap().push(String::from_utf8_lossy(&request).to_lowercase());
+                let response = responses.next().unwrap_or("HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    fn client(retries: u32, backoff: Duration) -> Client {
+        Client::new(Policy { timeout: Duration::from_secs(5), retries, backoff })
+    }
+
+    #[test]
+    fn retries_server_errors_and_resumes_partial_downloads() {
+        let (url, requests) = serve(vec![
+            "HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\ncontent-length: 10\r\nconnection: close\r\n\r\nhello",
+            "HTTP/1.1 206 Partial Content\r\ncontent-range: bytes 5-9/10\r\ncontent-length: 5\r\nconnection: close\r\n\r\nworld",
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("pkg.jar");
+
+        task::block_on(client(3, Duration::from_millis(10)).download(&format!("{url}/pkg.jar"), target.as_path().into(), None))
+            .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "helloworld");
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[2].replace(' ', "").contains("range:bytes=5-"));
+    }
+
+    #[test]
+    fn waits_for_retry_after() {
+        let (url, _) = serve(vec![
+            "HTTP/1.1 429 Too Many Requests\r\nretry-after: 1\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+            "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+        ]);
+
+        let start = Instant::now();
+        let body = task::block_on(client(1, Duration::from_millis(10)).download_string(&url, None)).unwrap();
+
+        assert_eq!(body.as_deref(), Some("ok"));
+        assert!(start.elapsed() >= Duration::from_millis(900));
+    }
+
+    #[test]
+    fn sends_repo_credentials() {
+        let (url, requests) = serve(vec!["HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok"]);
+        let credentials = Credentials::Basic { username: "me".into(), password: "secret".into() };
+
+        task::block_on(client(0, Duration::from_millis(10)).download_string(&url, Some(&credentials))).unwrap();
+
+        // base64 of me:secret
+        assert!(requests.lock().unwrap()[0].replace(' ', "").contains("authorization:basicbwu6c2vjcmv0"));
+    }
+
+    #[test]
+    fn records_unverified_downloads() {
+        let not_found = "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+        let (url, requests) = serve(vec!["HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok", not_found, not_found]);
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("pkg.jar");
+
+        let result = task::block_on(client(0, Duration::from_millis(10)).create_target_and_download(
+            &format!("{url}/pkg.jar"),
+            target.as_path().into(),
+            None,
+        ));
+
+        assert!(result.is_downloaded());
+        assert_eq!(requests.lock().unwrap().len(), 3); // the jar, its .sha256 and .sha1
+        assert_eq!(std::fs::read_to_string(Checksum::unverified(&target)).unwrap(), Checksum::Sha256.digest(&target).unwrap());
+        assert!(!Checksum::Sha256.sidecar(&target).exists());
+    }
+
+    #[test]
+    fn refuses_downloads_when_the_checksum_cannot_be_fetched() {
+        let (url, _) = serve(vec![
+            "HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+            "HTTP/1.1 403 Forbidden\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("pkg.jar");
+
+        let result = task::block_on(client(0, Duration::from_millis(10)).create_target_and_download(
+            &format!("{url}/pkg.jar"),
+            target.as_path().into(),
+            None,
+        ));
+
+        assert!(!result.is_downloaded());
+        assert!(!target.exists());
+        assert!(!Checksum::unverified(&target).exists());
+    }
+
+    #[test]
+    fn does_not_retry_client_errors() {
+        let (url, requests) = serve(vec!["HTTP/1.1 403 Forbidden\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"]);
+
+        let result = task::block_on(client(3, Duration::from_millis(10)).download_string(&url, None));
+
+        assert!(result.is_err());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,107 @@
+use std::future::Future;
+use std::time::{Duration, SystemTime};
+
+use anyhow::anyhow;
+use async_std::{future, task};
+use surf::http::other::RetryAfter;
+use surf::{Response, StatusCode};
+
+const BACKOFF: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// How requests are timed out and retried, from the `[fetch]` section of the manifest.
+#[derive(Clone, Copy, Debug)]
+pub struct Policy {
+    pub timeout: Duration,
+    pub retries: u32,
+    pub backoff: Duration,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Policy::from(&manifest::fetch::Fetch::default())
+    }
+}
+
+impl From<&manifest::fetch::Fetch> for Policy {
+    fn from(fetch: &manifest::fetch::Fetch) -> Self {
+        Policy {
+            timeout: fetch.timeout,
+            retries: fetch.retries,
+            backoff: BACKOFF,
+        }
+    }
+}
+
+/// Why an attempt failed, and whether it is worth another one.
+#[derive(Debug)]
+pub struct Failure {
+    err: anyhow::Error,
+    retryable: bool,
+    after: Option<Duration>,
+}
+
+impl Failure {
+    pub fn fatal(err: impl Into<anyhow::Error>) -> Failure {
+        Failure { err: err.into(), retryable: false, after: None }
+    }
+
+    pub fn retry(err: impl Into<anyhow::Error>) -> Failure {
+        Failure { err: err.into(), retryable: true, after: None }
+    }
+
+    /// 5xx and 408 are retried with backoff, 429 after its `Retry-After`, anything else is final.
+    pub fn status(response: &Response) -> Failure {
+        let status = response.status();
+        let err = anyhow!(status.to_string());
+
+        match status {
+            StatusCode::TooManyRequests => Failure {
+                after: retry_after(response),
+                ..Failure::retry(err)
+            },
+            StatusCode::RequestTimeout => Failure::retry(err),
+            _ if status.is_server_error() => Failure::retry(err),
+            _ => Failure::fatal(err),
+        }
+    }
+}
+
+/// `Retry-After` in seconds or as a date, capped so a server cannot stall the fetch.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let headers: &surf::http::Headers = response.as_ref();
+    let retry_after = RetryAfter::from_headers(headers).ok()??;
+    let after = retry_after.duration_since(SystemTime::now()).unwrap_or_default();
+    Some(after.min(MAX_DELAY))
+}
+
+impl Policy {
+    /// Exponential backoff: backoff, 2 * backoff, 4 * backoff ...
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff.saturating_mul(1 << attempt.min(16)).min(MAX_DELAY)
+    }
+
+    /// Run `attempt` until it succeeds, fails for good, or the retries are used up.
+    /// Every attempt is cancelled after the timeout.
+    pub async fn run<T, F, Fut>(&self, mut attempt: F) -> anyhow::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Failure>>,
+    {
+        let mut retry = 0;
+        loop {
+            let failure = match future::timeout(self.timeout, attempt()).await {
+                Ok(Ok(value)) => return Ok(value),
+                Ok(Err(failure)) => failure,
+                Err(_) => Failure::retry(anyhow!("timed out after {}s", self.timeout.as_secs_f32())),
+            };
+
+            if !failure.retryable || retry >= self.retries {
+                return Err(failure.err);
+            }
+
+            task::sleep(failure.after.unwrap_or_else(|| self.delay(retry))).await;
+            retry += 1;
+        }
+    }
+}
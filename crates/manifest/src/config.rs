@@ -6,15 +6,37 @@ use crate::{home::Home, Manifest};
 pub struct BuildK {
     pub home: Home,
     pub manifest: Option<Manifest>, // not needed if defaults are used
+    pub manifest_error: Option<String>, // why buildk.toml could not be loaded
+    pub offline: bool, // only use what is in ~/.buildk/cache
+    pub daemon: bool, // compile with the long-lived compile server
 }
 
 impl BuildK  {
     pub fn new() -> Self {
+        let (manifest, manifest_error) = match Manifest::try_new() {
+            Ok(manifest) => (Some(manifest), None),
+            Err(err) if std::path::Path::new("buildk.toml").exists() => (None, Some(format!("{err:#}"))),
+            Err(_) => (None, None),
+        };
+
         BuildK  {
             home: Home::default(),
-            manifest: Manifest::try_new().ok(),
+            manifest,
+            manifest_error,
+            offline: false,
+            daemon: true,
         }
     }
+
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    pub fn daemon(mut self, daemon: bool) -> Self {
+        self.daemon = daemon;
+        self
+    }
 }
 
 impl Display for BuildK  {
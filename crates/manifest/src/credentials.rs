@@ -0,0 +1,86 @@
+use std::fmt::Debug;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use toml_edit::DocumentMut;
+
+pub const CREDENTIALS: &str = "credentials.toml";
+
+/// Credentials of a repository, from the environment or `~/.buildk/credentials.toml`, never from buildk.toml.
+///
+/// ```toml
+/// [nexus]
+/// username = "me"
+/// password = "secret"
+///
+/// [github]
+/// token = "ghp_..."
+/// ```
+///
+/// `BUILDK_REPO_<NAME>_USERNAME` and `_PASSWORD`, or `_TOKEN`, take precedence over the file.
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub enum Credentials {
+    Basic { username: String, password: String },
+    Bearer(String),
+}
+
+impl Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Credentials::Basic { username, .. } => write!(f, "Basic({username}, ***)"),
+            Credentials::Bearer(_) => write!(f, "Bearer(***)"),
+        }
+    }
+}
+
+impl Credentials {
+    /// Credentials of the repository named `repo`, if any.
+    pub fn find(repo: &str, home: &Path) -> Result<Option<Credentials>> {
+        if let Some(credentials) = Self::from_env(repo) {
+            return Ok(Some(credentials));
+        }
+
+        let path = home.join(CREDENTIALS);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let toml = crate::read_file(&path)?
+            .parse::<DocumentMut>()
+            .with_context(|| format!("{} is not valid TOML.", path.display()))?;
+
+        Ok(Self::from_toml(&toml, repo))
+    }
+
+    fn from_env(repo: &str) -> Option<Credentials> {
+        let prefix = format!("BUILDK_REPO_{}", env_name(repo));
+        let var = |suffix: &str| std::env::var(format!("{prefix}_{suffix}")).ok();
+
+        match (var("TOKEN"), var("USERNAME"), var("PASSWORD")) {
+            (Some(token), _, _) => Some(Credentials::Bearer(token)),
+            (None, Some(username), Some(password)) => Some(Credentials::Basic { username, password }),
+            _ => None,
+        }
+    }
+
+    fn from_toml(toml: &DocumentMut, repo: &str) -> Option<Credentials> {
+        let table = toml.get(repo)?.as_table_like()?;
+        let string = |key: &str| table.get(key).and_then(|it| it.as_str()).map(str::to_owned);
+
+        match (string("token"), string("username"), string("password")) {
+            (Some(token), _, _) => Some(Credentials::Bearer(token)),
+            (None, Some(username), Some(password)) => Some(Credentials::Basic { username, password }),
+            _ => None,
+        }
+    }
+}
+
+/// `my-nexus` -> `MY_NEXUS`
+fn env_name(repo: &str) -> String {
+    repo.chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect()
+}
@@ -0,0 +1,243 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use dependency::{Package, PackageKind};
+use toml_edit::{value, DocumentMut, Item, Table, TableLike, Value};
+
+use crate::packages::artifact;
+
+/// Edits the dependency tables of buildk.toml in place, comments and formatting are kept.
+pub struct ManifestEditor {
+    path: PathBuf,
+    toml: DocumentMut,
+}
+
+impl ManifestEditor {
+    pub fn open(dir: &Path) -> Result<ManifestEditor> {
+        let path = dir.join("buildk.toml");
+        let toml = crate::read_file(&path)?
+            .parse::<DocumentMut>()
+            .with_context(|| format!("{} is not valid TOML.", path.display()))?;
+
+        Ok(ManifestEditor { path, toml })
+    }
+
+    /// Add `pkg` to the table of its kind, `"<namespace>:<name>" = "<version>"`.
+    /// An artifact already in that table gets the new version, returns the version it had.
+    pub fn add(&mut self, pkg: &Package) -> Result<Option<String>> {
+        let section = section(&pkg.kind)?;
+        let table = self
+            .toml
+            .entry(section)
+            .or_insert(Item::Table(Table::new()))
+            .as_table_like_mut()
+            .with_context(|| format!("[{section}] is not a table"))?;
+
+        match entries(table, vec![]).into_iter().find(|(_, artifact)| is_same(artifact, pkg)) {
+            Some((path, _)) => {
+                let item = get_mut(table, &path).and_then(|it| it.as_value_mut()).context("artifact vanished")?;
+                match item.as_inline_table_mut() {
+                    Some(inline) => match inline.get_mut("version") {
+                        Some(version) => Ok(replace(version, &pkg.version)),
+                        None => {
+                            inline.insert("version", pkg.version.as_str().into());
+                            Ok(None)
+                        }
+                    },
+                    None => Ok(replace(item, &pkg.version)),
+                }
+            }
+            None => {
+                table.insert(&key(pkg), value(&pkg.version));
+                Ok(None)
+            }
+        }
+    }
+
+    /// The artifacts declared in the dependency tables, with the versions as written.
+    pub fn declared(&self) -> Vec<Package> {
+        let mut declared = vec![];
+
+        for kind in [PackageKind::Compile, PackageKind::Runtime, PackageKind::Test] {
+            let Some(table) = section(&kind).ok().and_then(|it| self.toml.get(it)).and_then(|it| it.as_table_like()) else {
+                continue;
+            };
+
+            for (path, (namespace, name)) in entries(table, vec![]) {
+                let version = get(table, &path)
+                    .and_then(|it| it.as_value())
+                    .and_then(|it| match it.as_inline_table() {
+                        Some(inline) => inline.get("version").and_then(|it| it.as_str()),
+                        None => it.as_str(),
+                    })
+                    .unwrap_or_default();
+                declared.push(Package::new(name, namespace, version.to_owned(), kind.clone()));
+            }
+        }
+
+        declared
+    }
+
+    /// Remove `<namespace>:<name>`, or `<name>` in any namespace, from the dependency tables.
+    /// Returns the kinds it was removed from.
+    pub fn remove(&mut self, namespace: Option<&str>, name: &str) -> Vec<PackageKind> {
+        let mut removed = vec![];
+
+        for kind in [PackageKind::Compile, PackageKind::Runtime, PackageKind::Test] {
+            let Some(table) = section(&kind).ok().and_then(|it| self.toml.get_mut(it)).and_then(|it| it.as_table_like_mut()) else {
+                continue;
+            };
+
+            let matching = entries(table, vec![])
+                .into_iter()
+                .filter(|(_, (ns, artifact))| artifact == name && (namespace.is_none() || ns.as_deref() == namespace))
+                .collect::<Vec<_>>();
+
+            for (path, _) in matching.iter() {
+                remove(table, path);
+            }
+            if !matching.is_empty() {
+                removed.push(kind);
+            }
+        }
+
+        removed
+    }
+
+    pub fn save(&self) -> Result<()> {
+        std::fs::write(&self.path, self.toml.to_string()).with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+fn section(kind: &PackageKind) -> Result<&'static str> {
+    let section = match kind {
+        PackageKind::Compile => "compile",
+        PackageKind::Runtime => "runtime",
+        PackageKind::Test => "test",
+        _ => anyhow::bail!("{} dependencies are not declared in buildk.toml", Into::<String>::into(kind.clone())),
+    };
+    Ok(section)
+}
+
+/// Replace a value, keeping its whitespace and comments.
+fn replace(item: &mut Value, version: &str) -> Option<String> {
+    let previous = item.as_str().map(str::to_owned);
+    let decor = item.decor().clone();
+    *item = version.into();
+    *item.decor_mut() = decor;
+    previous
+}
+
+fn key(pkg: &Package) -> String {
+    match &pkg.namespace {
+        Some(namespace) => format!("{namespace}:{}", pkg.name),
+        None => pkg.name.clone(),
+    }
+}
+
+type Artifact = (Option<String>, String);
+
+fn is_same(artifact: &Artifact, pkg: &Package) -> bool {
+    artifact.0 == pkg.namespace && artifact.1 == pkg.name
+}
+
+/// Every declared artifact with the path of keys to it, dotted keys are joined like `decend` does.
+fn entries(table: &dyn TableLike, keys: Vec<String>) -> Vec<(Vec<String>, Artifact)> {
+    let mut found = vec![];
+
+    for (key, item) in table.iter() {
+        let mut path = keys.clone();
+        path.push(key.to_owned());
+
+        match item {
+            Item::Value(value) if value.as_inline_table().is_some_and(|it| it.contains_key("path")) => {} // a module
+            Item::Value(_) => {
+                // the manifest fails to load with an invalid key, there's nothing to remove
+                if let Ok(artifact) = artifact(&path.join(".")) {
+                    found.push((path, artifact));
+                }
+            }
+            Item::Table(table) => found.extend(entries(table, path)),
+            _ => {}
+        }
+    }
+
+    found
+}
+
+fn get<'a>(table: &'a dyn TableLike, path: &[String]) -> Option<&'a Item> {
+    match path {
+        [key] => table.get(key),
+        [key, rest @ ..] => get(table.get(key)?.as_table_like()?, rest),
+        [] => None,
+    }
+}
+
+fn get_mut<'a>(table: &'a mut dyn TableLike, path: &[String]) -> Option<&'a mut Item> {
+    match path {
+        [key] => table.get_mut(key),
+        [key, rest @ ..] => get_mut(table.get_mut(key)?.as_table_like_mut()?, rest),
+        [] => None,
+    }
+}
+
+/// Remove the value at `path`, and the dotted tables left empty.
+fn remove(table: &mut dyn TableLike, path: &[String]) {
+    match path {
+        [key] => {
+            table.remove(key);
+        }
+        [key, rest @ ..] => {
+            let Some(child) = table.get_mut(key).and_then(|it| it.as_table_like_mut()) else {
+                return;
+            };
+            remove(child, rest);
+            if child.is_empty() {
+                table.remove(key);
+            }
+        }
+        [] => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dependency::{Package, PackageKind};
+
+    use super::ManifestEditor;
+
+    #[test]
+    fn edits_keep_comments_and_formatting() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(
+            dir.join("buildk.toml"),
+            "# my project\n[compile]\norg.slf4j_slf4j-api = \"2.0.12\" # logging\nio.ktor_ktor-client = { version = \"2.3.0\", exclude = [\"*\"] }\n",
+        )
+        .unwrap();
+
+        let mut editor = ManifestEditor::open(dir).unwrap();
+        let pkg = |namespace: &str, name: &str, version: &str, kind| Package::new(name.into(), Some(namespace.into()), version.into(), kind);
+
+        assert_eq!(editor.add(&pkg("org.slf4j", "slf4j-api", "2.0.13", PackageKind::Compile)).unwrap(), Some("2.0.12".into()));
+        assert_eq!(editor.add(&pkg("io.ktor", "ktor-client", "2.3.1", PackageKind::Compile)).unwrap(), Some("2.3.0".into()));
+        assert_eq!(editor.add(&pkg("io.mockk", "mockk", "1.13.11", PackageKind::Test)).unwrap(), None);
+        editor.save().unwrap();
+
+        let content = std::fs::read_to_string(dir.join("buildk.toml")).unwrap();
+        assert!(content.starts_with("# my project\n[compile]\norg.slf4j_slf4j-api = \"2.0.13\" # logging\n"));
+        assert!(content.contains("io.ktor_ktor-client = { version = \"2.3.1\", exclude = [\"*\"] }"));
+        assert!(content.contains("[test]\n\"io.mockk:mockk\" = \"1.13.11\""));
+
+        let mut editor = ManifestEditor::open(dir).unwrap();
+        assert_eq!(editor.remove(None, "slf4j-api"), [PackageKind::Compile]);
+        assert_eq!(editor.remove(Some("io.mockk"), "mockk"), [PackageKind::Test]);
+        assert!(editor.remove(None, "missing").is_empty());
+        editor.save().unwrap();
+
+        let content = std::fs::read_to_string(dir.join("buildk.toml")).unwrap();
+        assert!(!content.contains("slf4j"));
+        assert!(!content.contains("mockk"));
+        assert!(content.contains("# my project"));
+    }
+}
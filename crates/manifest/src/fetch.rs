@@ -0,0 +1,65 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use toml_edit::DocumentMut;
+
+use crate::Section;
+
+/// How dependencies are downloaded, the `[fetch]` section.
+#[derive(Clone, Debug)]
+pub struct Fetch {
+    /// Concurrent downloads, `--jobs` overrides it
+    pub parallelism: Option<usize>,
+    /// Per request, until the whole response is received
+    pub timeout: Duration,
+    /// Attempts after the first for timeouts, connection errors, 5xx and 429
+    pub retries: u32,
+}
+
+impl Default for Fetch {
+    fn default() -> Self {
+        Fetch {
+            parallelism: None,
+            timeout: Duration::from_secs(60),
+            retries: 3,
+        }
+    }
+}
+
+impl TryFrom<&DocumentMut> for Fetch {
+    type Error = anyhow::Error;
+
+    fn try_from(manifest: &DocumentMut) -> Result<Self> {
+        let mut fetch = Fetch::default();
+
+        let Some(table) = manifest
+            .as_table()
+            .into_iter()
+            .find_map(|(key, value)| match Section::from_str(key) {
+                Ok(Section::Fetch) => value.as_table(),
+                _ => None,
+            })
+        else {
+            return Ok(fetch);
+        };
+
+        let positive = |key: &str| match table.get(key).map(|it| it.as_integer()) {
+            None => Ok(None),
+            Some(Some(value)) if value > 0 => Ok(Some(value as u64)),
+            Some(_) => bail!("[fetch] {key} must be a positive integer"),
+        };
+
+        fetch.parallelism = positive("parallelism")?.map(|it| it as usize);
+        if let Some(seconds) = positive("timeout")? {
+            fetch.timeout = Duration::from_secs(seconds);
+        }
+        match table.get("retries").map(|it| it.as_integer()) {
+            None => {}
+            Some(Some(retries)) if retries >= 0 => fetch.retries = retries as u32,
+            Some(_) => bail!("[fetch] retries must be zero or a positive integer"),
+        }
+
+        Ok(fetch)
+    }
+}
@@ -1,5 +1,5 @@
 use std::fmt::{Display, Formatter};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Clone)]
 pub struct Home {
@@ -16,6 +16,13 @@ impl Default for Home {
     }
 }
 
+impl Home {
+    /// `~/.buildk`
+    pub fn dir(&self) -> &Path {
+        &self.path
+    }
+}
+
 pub fn cache_location() -> PathBuf {
     home::home_dir()
         .expect("home directory")
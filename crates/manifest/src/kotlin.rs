@@ -0,0 +1,208 @@
+use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use toml_edit::{DocumentMut, Item};
+use util::hasher::StableHasher;
+
+use crate::Section;
+
+/// Options passed to kotlinc, the `[kotlin.options]` section.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KotlinOptions {
+    /// `-jvm-target`, e.g. 17
+    pub jvm_target: Option<String>,
+    /// `-language-version`, e.g. 2.0
+    pub language_version: Option<String>,
+    /// `-api-version`, e.g. 1.9
+    pub api_version: Option<String>,
+    /// `-Xjsr305`, one of ignore, warn or strict
+    pub jsr305: Option<String>,
+    /// `-opt-in` for every annotation
+    pub opt_in: Vec<String>,
+    /// `-progressive`
+    pub progressive: bool,
+    /// `-Werror`
+    pub warnings_as_errors: bool,
+    /// `-Xcontext-receivers`
+    pub context_receivers: bool,
+    /// Passed to kotlinc as is, after the options above
+    pub extra_args: Vec<String>,
+}
+
+impl KotlinOptions {
+    /// The kotlinc arguments, in the order of the fields.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![];
+        let mut pair = |flag: &str, value: &Option<String>| {
+            if let Some(value) = value {
+                args.extend([flag.to_owned(), value.to_owned()]);
+            }
+        };
+        pair("-jvm-target", &self.jvm_target);
+        pair("-language-version", &self.language_version);
+        pair("-api-version", &self.api_version);
+
+        if let Some(jsr305) = &self.jsr305 {
+            args.push(format!("-Xjsr305={jsr305}"));
+        }
+        args.extend(self.opt_in.iter().map(|annotation| format!("-opt-in={annotation}")));
+        if self.progressive {
+            args.push("-progressive".to_owned());
+        }
+        if self.warnings_as_errors {
+            args.push("-Werror".to_owned());
+        }
+        if self.context_receivers {
+            args.push("-Xcontext-receivers".to_owned());
+        }
+        args.extend(self.extra_args.iter().cloned());
+        args
+    }
+
+    /// Changes when the arguments change, 0 without options like caches written before they existed.
+    pub fn fingerprint(&self) -> u64 {
+        let args = self.args();
+        if args.is_empty() {
+            return 0;
+        }
+
+        let mut hasher = StableHasher::default();
+        args.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl TryFrom<&DocumentMut> for KotlinOptions {
+    type Error = anyhow::Error;
+
+    fn try_from(manifest: &DocumentMut) -> Result<Self> {
+        let mut options = KotlinOptions::default();
+
+        let Some(table) = manifest
+            .as_table()
+            .into_iter()
+            .find_map(|(key, value)| match Section::from_str(key) {
+                Ok(Section::Kotlin) => value.as_table_like()?.get("options")?.as_table_like(),
+                _ => None,
+            })
+        else {
+            return Ok(options);
+        };
+
+        for (key, value) in table.iter() {
+            match key {
+                "jvm_target" => options.jvm_target = Some(version(key, value)?),
+                "language_version" => options.language_version = Some(version(key, value)?),
+                "api_version" => options.api_version = Some(version(key, value)?),
+                "jsr305" => match value.as_str() {
+                    Some(mode @ ("ignore" | "warn" | "strict")) => options.jsr305 = Some(mode.to_owned()),
+                    _ => bail!("[kotlin.options] jsr305 must be \"ignore\", \"warn\" or \"strict\""),
+                },
+                "opt_in" => options.opt_in = strings(key, value)?,
+                "progressive" => options.progressive = flag(key, value)?,
+                "warnings_as_errors" => options.warnings_as_errors = flag(key, value)?,
+                "context_receivers" => options.context_receivers = flag(key, value)?,
+                "extra_args" => options.extra_args = strings(key, value)?,
+                _ => bail!("[kotlin.options] unknown option {key}, kotlinc arguments without an option go in extra_args"),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// `17` or `"17"`, `"2.0"`
+fn version(key: &str, value: &Item) -> Result<String> {
+    match (value.as_str(), value.as_integer()) {
+        (Some(version), _) => Ok(version.to_owned()),
+        (_, Some(version)) => Ok(version.to_string()),
+        _ => bail!("[kotlin.options] {key} must be a version string"),
+    }
+}
+
+fn flag(key: &str, value: &Item) -> Result<bool> {
+    match value.as_bool() {
+        Some(flag) => Ok(flag),
+        None => bail!("[kotlin.options] {key} must be true or false"),
+    }
+}
+
+fn strings(key: &str, value: &Item) -> Result<Vec<String>> {
+    let strings = value
+        .as_array()
+        .map(|array| array.iter().map(|it| it.as_str().map(str::to_owned)).collect::<Option<Vec<_>>>());
+
+    match strings {
+        Some(Some(strings)) => Ok(strings),
+        _ => bail!("[kotlin.options] {key} must be an array of strings"),
+    }
+}
+
+impl Display for KotlinOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.args() {
+            args if args.is_empty() => Ok(()),
+            args => writeln!(f, "{:<26}{}", "kotlin.options", args.join(" ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KotlinOptions;
+
+    fn options(toml: &str) -> anyhow::Result<KotlinOptions> {
+        KotlinOptions::try_from(&toml.parse::<toml_edit::DocumentMut>().unwrap())
+    }
+
+    #[test]
+    fn options_to_args() {
+        let options = options(
+            r#"
+            [kotlin]
+            path = "/opt/kotlin"
+
+            [kotlin.options]
+            jvm_target = 17
+            language_version = "2.0"
+            jsr305 = "strict"
+            opt_in = ["kotlin.RequiresOptIn", "kotlinx.coroutines.ExperimentalCoroutinesApi"]
+            warnings_as_errors = true
+            extra_args = ["-Xno-param-assertions"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            options.args(),
+            [
+                "-jvm-target",
+                "17",
+                "-language-version",
+                "2.0",
+                "-Xjsr305=strict",
+                "-opt-in=kotlin.RequiresOptIn",
+                "-opt-in=kotlinx.coroutines.ExperimentalCoroutinesApi",
+                "-Werror",
+                "-Xno-param-assertions",
+            ]
+        );
+    }
+
+    #[test]
+    fn no_options() {
+        let options = options("[kotlin]\npath = \"/opt/kotlin\"").unwrap();
+
+        assert_eq!(options, KotlinOptions::default());
+        assert_eq!(options.fingerprint(), 0);
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(options("[kotlin.options]\njsr305 = \"loud\"").is_err());
+        assert!(options("[kotlin.options]\nprogressive = \"yes\"").is_err());
+        assert!(options("[kotlin.options]\njvm-target = \"17\"").is_err());
+    }
+}
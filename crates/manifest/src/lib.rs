@@ -3,15 +3,27 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use anyhow::{Context, Result};
+use dependency::resolver::{self, Resolution, Strategy};
+use dependency::{Package, PackageKind};
+use fetch::Fetch;
+use kotlin::KotlinOptions;
+use lock::Lockfile;
 use packages::Packages;
 use project::Project;
 use repos::Repos;
+use workspace::Workspace;
 
 pub mod config;
+pub mod credentials;
+pub mod edit;
+pub mod fetch;
 pub mod home;
+pub mod kotlin;
+pub mod lock;
 pub mod packages;
 pub mod project;
 pub mod repos;
+pub mod workspace;
 
 pub fn read_file(file: &Path) -> Result<String> {
     std::fs::read_to_string(file).context(format!("File not found: {}", file.display()))
@@ -25,6 +37,10 @@ pub(crate) enum Section {
     TestDeps,
     Kotlin,
     Java,
+    Workspace,
+    Resolution,
+    Fetch,
+    Mirrors,
 }
 
 impl FromStr for Section {
@@ -39,6 +55,10 @@ impl FromStr for Section {
             "test" => Section::TestDeps,
             "kotlin" => Section::Kotlin,
             "java" => Section::Java,
+            "workspace" => Section::Workspace,
+            "resolution" => Section::Resolution,
+            "fetch" => Section::Fetch,
+            "mirrors" => Section::Mirrors,
             _ => anyhow::bail!("Invalid section: {}", s),
         })
     }
@@ -52,14 +72,26 @@ pub struct Manifest {
     pub runtime_deps: Packages,
     pub test_deps: Packages,
     pub kotlin_home: Option<PathBuf>,
+    pub kotlin_options: KotlinOptions,
     pub java_home: Option<PathBuf>,
     pub all_packages: Packages, // TODO: can we remove this?
+    pub modules: Vec<PathBuf>, // other buildk projects depended on by path
+    pub workspace: Option<Workspace>,
+    pub strategy: Strategy,
+    pub fetch: Fetch,
+    pub dir: PathBuf,
+    pub lock: Option<Lockfile>,
 }
 
 impl Manifest {
     pub fn try_new() -> anyhow::Result<Manifest> {
-        let path = Path::new("buildk.toml");
-        let content = read_file(path).context("buildk.toml not found.")?;
+        let cwd = std::env::current_dir().context("path to current working directory")?;
+        Self::try_from_dir(&cwd)
+    }
+
+    pub fn try_from_dir(dir: &Path) -> anyhow::Result<Manifest> {
+        let path = dir.join("buildk.toml");
+        let content = read_file(&path).context("buildk.toml not found.")?;
 
         let toml = match content.parse().context("Manifest not valid TOML.") {
             Ok(toml) => toml,
@@ -69,19 +101,168 @@ impl Manifest {
             }
         };
 
-        let packages = Packages::from(&toml);
+        let repos = Repos::try_from(&toml)?;
+        let maven_local = repos.local().and_then(|it| it.local_path());
+        let mut packages = Packages::try_from(&toml)?;
+        packages.pkgs.iter_mut().for_each(|pkg| pkg.maven_local = maven_local.clone());
+        let mut lock = Lockfile::read(dir)?;
+        if let Some(lock) = &mut lock {
+            lock.declared
+                .iter_mut()
+                .chain(lock.packages.iter_mut().map(|locked| &mut locked.package))
+                .for_each(|pkg| pkg.maven_local = maven_local.clone());
+        }
 
         Ok(Manifest {
-            project: Project::from(toml.clone()),
-            repos: Repos::from(toml.clone()),
+            project: Project::from_toml(&toml, dir),
+            repos,
             compile_deps: Packages::new(packages.compile()),
             runtime_deps: Packages::new(packages.runtime()),
             test_deps: Packages::new(packages.test()),
             kotlin_home: kotlin_home(&toml),
+            kotlin_options: KotlinOptions::try_from(&toml)?,
             java_home: java_home(&toml),
             all_packages: packages,
+            modules: modules(&toml, dir),
+            workspace: Workspace::try_new(dir, &toml)?,
+            strategy: strategy(&toml)?,
+            fetch: Fetch::try_from(&toml)?,
+            dir: dir.to_path_buf(),
+            lock,
         })
     }
+
+    /// The `mavenLocal` repo packages missing from the cache are read from, set on every package of this manifest.
+    pub fn maven_local(&self) -> Option<PathBuf> {
+        self.repos.local().and_then(|it| it.local_path())
+    }
+
+    /// Resolve the manifested packages of the given kinds and their transitives to one version per module.
+    /// An up-to-date `buildk.lock` is used as is.
+    pub fn resolve(&self, kinds: &[PackageKind]) -> Resolution {
+        match self.up_to_date_lock() {
+            Some(lock) => Resolution { selected: lock.packages(kinds), conflicts: vec![] },
+            None => self.resolve_unlocked(kinds, &[]),
+        }
+    }
+
+    /// Resolve without the lock, modules in `pins` keep the pinned version.
+    /// The versions managed by declared boms are pinned after `pins`.
+    pub fn resolve_unlocked(&self, kinds: &[PackageKind], pins: &[Package]) -> Resolution {
+        let roots = self.roots(kinds);
+        let pins = pins
+            .iter()
+            .cloned()
+            .chain(self.constraints().into_iter().filter(|managed| !self.declares_version(managed)))
+            .collect::<Vec<_>>();
+
+        resolver::resolve_pinned(&roots, self.strategy, &pins)
+    }
+
+    /// The declared packages of the given kinds, packages declared without version get the version of a bom.
+    /// They are left out until the bom is downloaded.
+    pub fn roots(&self, kinds: &[PackageKind]) -> Vec<Package> {
+        let constraints = self.constraints();
+
+        self.all_packages.pkgs
+            .iter()
+            .filter(|pkg| kinds.contains(&pkg.kind))
+            .filter_map(|pkg| match pkg.version.is_empty() {
+                false => Some(pkg.clone().resolve_version()),
+                true => constraints
+                    .iter()
+                    .find(|managed| managed.namespace == pkg.namespace && managed.name == pkg.name)
+                    .map(|managed| {
+                        Package::new(pkg.name.clone(), pkg.namespace.clone(), managed.version.clone(), pkg.kind.clone())
+                            .maven_local(pkg.maven_local.clone())
+                    }),
+            })
+            .collect()
+    }
+
+    /// Versions managed by the boms declared in the manifest, the first declared bom wins.
+    pub fn constraints(&self) -> Vec<Package> {
+        self.all_packages.pkgs
+            .iter()
+            .filter(|pkg| pkg.is_bom())
+            .flat_map(|bom| dependency::parser::maven::managed(&bom.location, bom.maven_local.as_deref()))
+            .collect()
+    }
+
+    fn declares_version(&self, pkg: &Package) -> bool {
+        self.all_packages.pkgs
+            .iter()
+            .any(|declared| declared.namespace == pkg.namespace && declared.name == pkg.name && !declared.version.is_empty())
+    }
+
+    /// The lock, if it was written for the packages currently declared.
+    pub fn up_to_date_lock(&self) -> Option<&Lockfile> {
+        self.lock
+            .as_ref()
+            .filter(|lock| lock.is_up_to_date(&self.all_packages.pkgs))
+    }
+
+    /// Resolved packages of the given kinds that are not downloaded.
+    pub fn missing(&self, kinds: &[PackageKind]) -> Vec<Package> {
+        self.resolve(kinds)
+            .selected
+            .into_iter()
+            .filter(|pkg| !pkg.is_cached())
+            .collect()
+    }
+
+    /// Jars of the resolved packages of the given kinds that are downloaded.
+    pub fn classpath(&self, kinds: &[PackageKind]) -> Vec<PathBuf> {
+        self.resolve(kinds)
+            .selected
+            .iter()
+            .map(|pkg| pkg.jar_absolute_path())
+            .filter(|jar| jar.exists())
+            .collect()
+    }
+
+    /// Compiled sources of the modules this project depends on by path.
+    pub fn module_outputs(&self) -> Vec<PathBuf> {
+        self.modules
+            .iter()
+            .filter_map(|dir| Manifest::try_from_dir(dir).ok())
+            .map(|module| module.project.out_paths().src)
+            .collect()
+    }
+}
+
+fn strategy(manifest: &toml_edit::DocumentMut) -> Result<Strategy> {
+    let strategy = manifest
+        .as_table()
+        .into_iter()
+        .find_map(|(key, value)| match Section::from_str(key) {
+            Ok(Section::Resolution) => value.get("strategy").and_then(|it| it.as_str()),
+            _ => None,
+        });
+
+    match strategy {
+        Some(strategy) => Strategy::from_str(strategy),
+        None => Ok(Strategy::default()),
+    }
+}
+
+/// Dependencies declared by path, e.g. `core = { path = "../core" }`
+fn modules(manifest: &toml_edit::DocumentMut, dir: &Path) -> Vec<PathBuf> {
+    manifest
+        .as_table()
+        .into_iter()
+        .flat_map(|(key, value)| match Section::from_str(key) {
+            Ok(Section::CompileDeps | Section::RuntimeDeps | Section::TestDeps) => match value.as_table() {
+                None => vec![],
+                Some(table) => table
+                    .iter()
+                    .filter_map(|(_, dep)| dep.as_table_like()?.get("path")?.as_str().map(|path| dir.join(path)))
+                    .map(|path| path.canonicalize().unwrap_or(path))
+                    .collect(),
+            },
+            _ => vec![],
+        })
+        .collect()
 }
 
 fn kotlin_home(manifest: &toml_edit::DocumentMut) -> Option<PathBuf> {
@@ -91,9 +272,10 @@ fn kotlin_home(manifest: &toml_edit::DocumentMut) -> Option<PathBuf> {
         .flat_map(|(key, value)| match Section::from_str(key) {
             Ok(Section::Kotlin) => match value.as_table() {
                 None => vec![],
+                // [kotlin.options] is a table too
                 Some(table) => table
                     .iter()
-                    .map(|(_, path)| PathBuf::from(path.as_str().unwrap()))
+                    .filter_map(|(_, path)| path.as_str().map(PathBuf::from))
                     .collect(),
             },
             _ => vec![],
@@ -127,13 +309,23 @@ impl Display for Manifest {
         write!(f, "{}", self.project)?;
 
         if self.kotlin_home.is_some() {
-            write!(f, "{}", self.kotlin_home.clone().unwrap().display())?;
+            writeln!(f, "{:<26}{}", "kotlin.path", self.kotlin_home.clone().unwrap().display())?;
         }
 
+        write!(f, "{}", self.kotlin_options)?;
+
         for repo in self.repos.repos.iter() {
             write!(f, "{}", repo)?;
         }
 
+        if let Some(workspace) = &self.workspace {
+            write!(f, "{}", workspace)?;
+        }
+
+        if let Some(lock) = &self.lock {
+            write!(f, "{}", lock)?;
+        }
+
         write!(f, "{:<26}{}", "Compile", self.compile_deps)?;
         write!(f, "{:<26}{}", "Runtime", self.runtime_deps)?;
         write!(f, "{:<26}{}", "Test", self.test_deps)
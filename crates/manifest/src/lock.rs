@@ -0,0 +1,288 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Context, Result};
+use dependency::resolver::Resolution;
+use dependency::{Exclusion, Package, PackageKind};
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, Table};
+
+pub const LOCKFILE: &str = "buildk.lock";
+
+const VERSION: i64 = 1;
+
+/// The resolved dependency graph written by `buildk fetch`, read back to resolve strictly from it.
+#[derive(Clone, Default)]
+pub struct Lockfile {
+    /// The packages declared in the manifest when the lock was written
+    pub declared: Vec<Package>,
+    pub packages: Vec<Locked>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Locked {
+    pub package: Package,
+    pub repository: Option<String>,
+    pub checksum: Option<String>,
+}
+
+impl Lockfile {
+    pub fn location(dir: &Path) -> PathBuf {
+        dir.join(LOCKFILE)
+    }
+
+    /// Read `buildk.lock` in `dir`, `None` when the project is not locked yet.
+    pub fn read(dir: &Path) -> Result<Option<Lockfile>> {
+        let path = Self::location(dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = crate::read_file(&path)?;
+        let toml = content
+            .parse::<DocumentMut>()
+            .with_context(|| format!("{} is not valid TOML.", path.display()))?;
+
+        let tables = |key: &str| match toml.get(key) {
+            None => Ok(vec![]),
+            Some(item) => item
+                .as_array_of_tables()
+                .with_context(|| format!("expected [[{key}]] tables in {}", path.display()))
+                .map(|tables| tables.iter().collect::<Vec<_>>()),
+        };
+
+        let declared = tables("declared")?
+            .into_iter()
+            .map(parse_package)
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Invalid {}", path.display()))?;
+
+        let packages = tables("package")?
+            .into_iter()
+            .map(parse_locked)
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Invalid {}", path.display()))?;
+
+        Ok(Some(Lockfile { declared, packages }))
+    }
+
+    /// Lock the selected packages of a resolution, `roots` are the packages declared in the manifest.
+    /// Packages already in the `previous` lock keep their checksum, a changed jar doesn't get locked again.
+    pub fn from_resolution(resolution: &Resolution, roots: &[Package], previous: Option<&Lockfile>) -> Lockfile {
+        let locked_checksum = |pkg: &Package| {
+            previous?
+                .packages
+                .iter()
+                .find(|locked| locked.package.coordinates() == pkg.coordinates())?
+                .checksum
+                .clone()
+        };
+
+        let packages = resolution
+            .selected
+            .iter()
+            .map(|pkg| Locked {
+                package: pkg.clone(),
+                repository: pkg.repository(),
+                checksum: locked_checksum(pkg).or_else(|| {
+                    util::hasher::sha256(&pkg.jar_absolute_path())
+                        .ok()
+                        .map(|hex| format!("sha256:{hex}"))
+                }),
+            })
+            .collect();
+
+        Lockfile { declared: roots.to_vec(), packages }
+    }
+
+    /// Fails with every cached jar that is not the one locked.
+    pub fn verify(&self) -> Result<()> {
+        let mismatches = self
+            .packages
+            .iter()
+            .filter_map(|locked| locked.verify().err())
+            .map(|err| format!("  {err}"))
+            .collect::<Vec<_>>();
+
+        if !mismatches.is_empty() {
+            bail!("cached jars differ from buildk.lock:\n{}", mismatches.join("\n"));
+        }
+
+        Ok(())
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<()> {
+        let mut toml = DocumentMut::new();
+        toml["version"] = value(VERSION);
+
+        let mut declared = ArrayOfTables::new();
+        for pkg in self.declared.iter() {
+            declared.push(package_table(pkg));
+        }
+        toml["declared"] = toml_edit::Item::ArrayOfTables(declared);
+
+        let mut packages = ArrayOfTables::new();
+        for locked in self.packages.iter() {
+            let mut table = package_table(&locked.package);
+            if let Some(repository) = &locked.repository {
+                table["repository"] = value(repository);
+            }
+            if let Some(checksum) = &locked.checksum {
+                table["checksum"] = value(checksum);
+            }
+            packages.push(table);
+        }
+        toml["package"] = toml_edit::Item::ArrayOfTables(packages);
+
+        let content = format!("# Generated by buildk, do not edit by hand.\n{toml}");
+        std::fs::write(Self::location(dir), content).context("Failed to write buildk.lock")
+    }
+
+    /// The locked packages of the given kinds.
+    pub fn packages(&self, kinds: &[PackageKind]) -> Vec<Package> {
+        self.packages
+            .iter()
+            .map(|locked| &locked.package)
+            .filter(|pkg| kinds.contains(&pkg.kind))
+            .cloned()
+            .collect()
+    }
+
+    /// Fails with the differences when the packages declared in the manifest are not the locked ones.
+    pub fn check(&self, declared: &[Package]) -> Result<()> {
+        let mut diff = vec![];
+        for pkg in declared.iter().filter(|pkg| !self.declared.contains(pkg)) {
+            diff.push(format!("  + {}", pkg.coordinates()));
+        }
+        for pkg in self.declared.iter().filter(|pkg| !declared.contains(pkg)) {
+            diff.push(format!("  - {}", pkg.coordinates()));
+        }
+
+        if !diff.is_empty() {
+            bail!("buildk.toml and buildk.lock disagree, run `buildk fetch` to update the lock:\n{}", diff.join("\n"));
+        }
+
+        Ok(())
+    }
+
+    pub fn is_up_to_date(&self, declared: &[Package]) -> bool {
+        self.check(declared).is_ok()
+    }
+}
+
+impl Locked {
+    /// Fails when the cached jar is not the one locked, a jar that isn't downloaded yet or wasn't locked passes.
+    pub fn verify(&self) -> Result<()> {
+        let jar = self.package.jar_absolute_path();
+        let (Some(checksum), true) = (&self.checksum, jar.is_file()) else {
+            return Ok(());
+        };
+
+        let coordinates = self.package.coordinates();
+        let expected = checksum
+            .strip_prefix("sha256:")
+            .with_context(|| format!("{coordinates}: unsupported checksum {checksum}"))?;
+        let actual = util::hasher::sha256(&jar).with_context(|| format!("{coordinates}: failed to read {}", jar.display()))?;
+        ensure!(actual == expected, "{coordinates}: {} has sha256:{actual}, locked {checksum}", jar.display());
+
+        Ok(())
+    }
+}
+
+fn package_table(pkg: &Package) -> Table {
+    let mut table = Table::new();
+    if let Some(namespace) = &pkg.namespace {
+        table["namespace"] = value(namespace);
+    }
+    table["name"] = value(&pkg.name);
+    table["version"] = value(&pkg.version);
+    table["kind"] = value(Into::<String>::into(pkg.kind.clone()).to_lowercase());
+    if !pkg.exclusions.is_empty() {
+        table["exclude"] = value(pkg.exclusions.iter().map(|it| it.to_string()).collect::<Array>());
+    }
+    table
+}
+
+fn string(table: &Table, key: &str) -> Option<String> {
+    table.get(key).and_then(|it| it.as_str()).map(str::to_owned)
+}
+
+fn parse_package(table: &Table) -> Result<Package> {
+    let (Some(name), Some(version)) = (string(table, "name"), string(table, "version")) else {
+        bail!("locked package without name or version");
+    };
+    let kind = string(table, "kind").map(PackageKind::from).unwrap_or_default();
+    let exclusions = match table.get("exclude").and_then(|it| it.as_array()) {
+        None => vec![],
+        Some(exclude) => exclude
+            .iter()
+            .filter_map(|it| it.as_str())
+            .map(Exclusion::from_str)
+            .collect::<Result<Vec<_>>>()?,
+    };
+
+    Ok(Package::new(name, string(table, "namespace"), version, kind).exclude(exclusions))
+}
+
+fn parse_locked(table: &Table) -> Result<Locked> {
+    Ok(Locked {
+        package: parse_package(table)?,
+        repository: string(table, "repository"),
+        checksum: string(table, "checksum"),
+    })
+}
+
+impl Display for Lockfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:<26}{} packages", "lock", self.packages.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dependency::{Package, PackageKind};
+
+    use super::{Locked, Lockfile};
+
+    #[test]
+    fn lock_round_trip() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+
+        let pkg = Package::new("kotlin-stdlib".into(), Some("org.jetbrains.kotlin".into()), "2.0.0".into(), PackageKind::Compile);
+        let lock = Lockfile {
+            declared: vec![pkg.clone()],
+            packages: vec![Locked {
+                package: pkg.clone(),
+                repository: Some("mavenCentral".into()),
+                checksum: Some("sha256:abc".into()),
+            }],
+        };
+        lock.write(dir).unwrap();
+
+        let read = Lockfile::read(dir).unwrap().unwrap();
+        assert_eq!(read.declared, lock.declared);
+        assert_eq!(read.packages, lock.packages);
+        assert!(read.check(&[pkg]).is_ok());
+        assert!(read.check(&[]).is_err());
+    }
+
+    #[test]
+    fn tampered_jar_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut pkg = Package::new("kotlin-stdlib".into(), Some("org.jetbrains.kotlin".into()), "2.0.0".into(), PackageKind::Compile);
+        pkg.location = dir.path().to_path_buf();
+
+        std::fs::write(dir.path().join("pkg.jar"), "the locked jar").unwrap();
+        let checksum = format!("sha256:{}", util::hasher::sha256(&dir.path().join("pkg.jar")).unwrap());
+        let lock = Lockfile {
+            declared: vec![pkg.clone()],
+            packages: vec![Locked { package: pkg, repository: None, checksum: Some(checksum) }],
+        };
+        assert!(lock.verify().is_ok());
+
+        std::fs::write(dir.path().join("pkg.jar"), "a tampered jar").unwrap();
+        let err = lock.verify().unwrap_err().to_string();
+        assert!(err.contains("org.jetbrains.kotlin:kotlin-stdlib:2.0.0"), "{err}");
+    }
+}
@@ -2,7 +2,7 @@ use std::str::FromStr;
 use std::{collections::BTreeMap, fmt::Display};
 
 use anyhow::Context;
-use dependency::{Package, PackageKind};
+use dependency::{Exclusion, Package, PackageKind};
 use toml_edit::{DocumentMut, Item, Table, Value};
 
 use crate::Section;
@@ -213,44 +213,40 @@ pub(crate) fn provided_pkgs() -> Vec<Package> {
     pub target_dir: PathBuf,
 }  */
 
-impl From<&DocumentMut> for Packages {
-    fn from(toml: &DocumentMut) -> Self {
-        let pkgs = dependencies(toml);
-        Packages { pkgs }
+impl TryFrom<&DocumentMut> for Packages {
+    type Error = anyhow::Error;
+
+    fn try_from(toml: &DocumentMut) -> anyhow::Result<Self> {
+        let pkgs = dependencies(toml)?;
+        Ok(Packages { pkgs })
     }
 }
 
-fn dependencies(manifest: &DocumentMut) -> Vec<Package> {
-    let manifested_deps = manifest
-        .as_table()
-        .into_iter()
-        .flat_map(|(key, value)| match Section::from_str(key) {
-            Ok(Section::CompileDeps) => match value.as_table() {
-                None => vec![],
-                Some(table) => dependencies_for(table, PackageKind::Compile),
-            },
-            Ok(Section::RuntimeDeps) => match value.as_table() {
-                None => vec![],
-                Some(table) => dependencies_for(table, PackageKind::Runtime),
-            },
-            Ok(Section::TestDeps) => match value.as_table() {
-                None => vec![],
-                Some(table) => dependencies_for(table, PackageKind::Test),
-            },
-            _ => vec![],
-        })
-        .collect::<Vec<Package>>();
+fn dependencies(manifest: &DocumentMut) -> anyhow::Result<Vec<Package>> {
+    let mut manifested_deps = vec![];
+    for (key, value) in manifest.as_table() {
+        let kind = match Section::from_str(key) {
+            Ok(Section::CompileDeps) => PackageKind::Compile,
+            Ok(Section::RuntimeDeps) => PackageKind::Runtime,
+            Ok(Section::TestDeps) => PackageKind::Test,
+            _ => continue,
+        };
+        if let Some(table) = value.as_table() {
+            manifested_deps.extend(dependencies_for(table, kind).with_context(|| format!("invalid [{key}]"))?);
+        }
+    }
 
     let provided = provided_pkgs();
 
-    manifested_deps
+    Ok(manifested_deps
         .iter()
         .chain(provided.iter())
         .cloned()
-        .collect()
+        .collect())
 }
 
-fn dependencies_for(table: &Table, kind: PackageKind) -> Vec<Package> {
+/// Modules, e.g. `lib = { path = "../lib" }`, are other buildk projects and not packages.
+fn dependencies_for(table: &Table, kind: PackageKind) -> anyhow::Result<Vec<Package>> {
     let mut map = BTreeMap::new();
 
     table.iter().for_each(|(key, value)| {
@@ -258,28 +254,50 @@ fn dependencies_for(table: &Table, kind: PackageKind) -> Vec<Package> {
     });
 
     map.into_iter()
-        .filter_map(|(key, value)| pkg_from_toml(kind.clone(), &key, value).ok())
+        .filter(|(_, value)| !value.as_inline_table().is_some_and(|it| it.contains_key("path")))
+        .map(|(key, value)| pkg_from_toml(kind.clone(), &key, value))
         .collect()
 }
 
+/// `"org.foo_bar" = "1.0"` or `"org.foo_bar" = { version = "1.0", exclude = ["commons-logging_commons-logging"] }`
 fn pkg_from_toml(kind: PackageKind, name: &str, item: &Value) -> anyhow::Result<Package> {
-    let version = item.as_str().context("missing version")?.to_string();
-
-    let artifact = name.split("_").collect::<Vec<&str>>();
-    let (name, namespace) = match artifact.len() {
-        1 => {
-            let name = artifact[0].to_string();
-            (name, None)
-        }
-        2 => {
-            let name = artifact[1].to_string();
-            let namespace = artifact[0].to_string();
-            (name, Some(namespace))
+    let (version, exclusions) = match item.as_inline_table() {
+        Some(table) => {
+            anyhow::ensure!(!table.contains_key("path"), "{name} is a module");
+            let version = table.get("version").and_then(|it| it.as_str()).unwrap_or_default();
+            let exclusions = match table.get("exclude") {
+                None => vec![],
+                Some(exclude) => exclude
+                    .as_array()
+                    .context("exclude must be an array")?
+                    .iter()
+                    .map(|it| Exclusion::from_str(it.as_str().context("exclusion must be a string")?))
+                    .collect::<anyhow::Result<Vec<_>>>()?,
+            };
+            (version.to_string(), exclusions)
         }
-        _ => panic!("unexpected artifact name"),
+        None => (item.as_str().context("missing version")?.to_string(), vec![]),
     };
 
-    Ok(Package::new(name, namespace, version, kind))
+    // an empty version is managed by a bom
+    let (namespace, name) = artifact(name)?;
+
+    Ok(Package::new(name, namespace, version, kind).exclude(exclusions))
+}
+
+/// `<namespace>:<name>`, `<namespace>_<name>` or `<name>`.
+/// Only the first separator counts, the name may contain `_`, e.g. `org.scala-lang.modules:scala-xml_2.13`.
+pub(crate) fn artifact(key: &str) -> anyhow::Result<(Option<String>, String)> {
+    let (namespace, name) = match key.split_once(':').or_else(|| key.split_once('_')) {
+        Some((namespace, name)) => (Some(namespace), name),
+        None => (None, key),
+    };
+    anyhow::ensure!(
+        !name.is_empty() && namespace.is_none_or(|it| !it.is_empty()),
+        "Invalid dependency {key} (expected <namespace>:<name>)"
+    );
+
+    Ok((namespace.map(str::to_owned), name.to_owned()))
 }
 
 /**
@@ -640,3 +658,36 @@ splendid.test.lib = "3.2.1"
         Ok(())
     }
 } */
+
+#[cfg(test)]
+mod tests {
+    use toml_edit::DocumentMut;
+
+    use super::{artifact, Packages};
+
+    #[test]
+    fn names_with_underscores() {
+        let toml = "[compile]\n\"org.scala-lang.modules:scala-xml_2.13\" = \"2.2.0\"\norg.slf4j_slf4j-api = \"2.0.13\"\n";
+        let packages = Packages::try_from(&toml.parse::<DocumentMut>().unwrap()).unwrap();
+
+        let scala = packages.pkgs.iter().find(|pkg| pkg.name == "scala-xml_2.13").unwrap();
+        assert_eq!(scala.namespace.as_deref(), Some("org.scala-lang.modules"));
+        let slf4j = packages.pkgs.iter().find(|pkg| pkg.name == "slf4j-api").unwrap();
+        assert_eq!(slf4j.namespace.as_deref(), Some("org.slf4j"));
+
+        assert_eq!(artifact("org.example_lib_2.13").unwrap(), (Some("org.example".into()), "lib_2.13".into()));
+        assert!(artifact("org.example:").is_err());
+    }
+
+    #[test]
+    fn malformed_dependencies_are_errors() {
+        let toml = "[compile]\nlib = { path = \"../lib\" }\n\"org.example:app\" = { version = \"1.0\", exclude = \"commons-logging\" }\n";
+        let err = Packages::try_from(&toml.parse::<DocumentMut>().unwrap()).err().unwrap();
+        assert_eq!(format!("{err:#}"), "invalid [compile]: exclude must be an array");
+
+        let toml = "[compile]\nlib = { path = \"../lib\" }\n\"org.example:app\" = \"1.0\"\n";
+        let packages = Packages::try_from(&toml.parse::<DocumentMut>().unwrap()).unwrap();
+        assert!(packages.pkgs.iter().any(|pkg| pkg.name == "app"));
+        assert!(!packages.pkgs.iter().any(|pkg| pkg.name == "lib"));
+    }
+}
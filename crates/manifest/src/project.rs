@@ -1,6 +1,6 @@
 use crate::Section;
 use std::fmt::Display;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use toml_edit::DocumentMut;
 
@@ -17,11 +17,10 @@ impl Project {
     pub fn out_paths(&self) -> ProjectOutput {
         ProjectOutput::new(&self)
     }
-}
 
-impl Default for Project {
-    fn default() -> Self {
-        let path = current_dir();
+    /// A project with default layout rooted in `dir`.
+    pub fn at(dir: &Path) -> Self {
+        let path = dir.to_path_buf();
 
         Project {
             main: String::from("Main.kt"),
@@ -33,6 +32,12 @@ impl Default for Project {
     }
 }
 
+impl Default for Project {
+    fn default() -> Self {
+        Project::at(&current_dir())
+    }
+}
+
 impl Display for Project {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "{:<26}{}", "project", self.path.display())?;
@@ -53,6 +58,7 @@ pub struct ProjectOutput {
     pub cache: PathBuf,
     pub test: PathBuf,
     pub test_report: PathBuf,
+    pub sarif: PathBuf,
     pub release: PathBuf,
 }
 
@@ -63,6 +69,7 @@ impl ProjectOutput {
             cache: project.out.join("cache.json"),
             test: project.out.join("test"),
             test_report: project.out.join("test-report"),
+            sarif: project.out.join("diagnostics.sarif"),
             release: project.out.join("app.jar"),
             path: project.out.clone(),
         }
@@ -76,6 +83,7 @@ impl Display for ProjectOutput {
         writeln!(f, "{:<26}{}", "project.out.src", self.src.display())?;
         writeln!(f, "{:<26}{}", "project.out.test", self.test.display())?;
         writeln!(f, "{:<26}{}", "project.out.test-report", self.test_report.display())?;
+        writeln!(f, "{:<26}{}", "project.out.sarif", self.sarif.display())?;
         writeln!(f, "{:<26}{}", "project.out.release", self.release.display())
     }
 }
@@ -86,14 +94,21 @@ fn current_dir() -> PathBuf {
 
 impl From<DocumentMut> for Project {
     fn from(value: DocumentMut) -> Self {
+        Project::from_toml(&value, &current_dir())
+    }
+}
+
+impl Project {
+    /// Read the `[project]` section, relative paths are resolved from the manifest directory `dir`.
+    pub fn from_toml(value: &DocumentMut, dir: &Path) -> Self {
         value
             .as_table()
             .into_iter()
             .filter_map(|(key, value)| {
                 if let Ok(Section::Project) = Section::from_str(key) {
                     if let Some(table) = value.as_table() {
-                        let path = table.get("path").map_or(current_dir(), |it| {
-                            PathBuf::from(it.as_str().expect("path to project"))
+                        let path = table.get("path").map_or(dir.to_path_buf(), |it| {
+                            dir.join(it.as_str().expect("path to project"))
                         });
 
                         let src = table
@@ -127,7 +142,7 @@ impl From<DocumentMut> for Project {
                 }
             })
             .next()
-            .unwrap_or_default()
+            .unwrap_or_else(|| Project::at(dir))
     }
 }
 
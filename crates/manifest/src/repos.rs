@@ -1,7 +1,11 @@
-use std::{fmt::Display, str::FromStr};
+use std::{fmt::Display, path::PathBuf, str::FromStr};
 
-use toml_edit::DocumentMut;
+use anyhow::{bail, Context, Result};
+use dependency::Package;
+use toml_edit::{DocumentMut, Item};
 
+use crate::credentials::Credentials;
+use crate::home::Home;
 use crate::Section;
 
 #[derive(Clone)]
@@ -9,31 +13,78 @@ pub struct Repos {
     pub repos: Vec<Repo>,
 }
 
-impl From<DocumentMut> for Repos {
-    fn from(value: DocumentMut) -> Self {
+impl TryFrom<&DocumentMut> for Repos {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &DocumentMut) -> Result<Self> {
         let mut repos = vec![Repo::default()];
+        let mut mirrors = vec![];
+
+        for (key, item) in value.as_table().iter() {
+            match (Section::from_str(key), item.as_table()) {
+                (Ok(Section::Repos), Some(table)) => repos.extend(parse(table)?),
+                (Ok(Section::Mirrors), Some(table)) => mirrors.extend(parse_mirrors(table)?),
+                _ => {}
+            }
+        }
+
+        let home = Home::default();
+        for repo in repos.iter_mut().filter(|repo| repo.local_path().is_none()) {
+            // a mirror of the repo by name wins over a mirror of all repos
+            let mirror = mirrors
+                .iter()
+                .find(|(of, _)| of == &repo.name)
+                .or_else(|| mirrors.iter().find(|(of, _)| of == "*"));
+            if let Some((_, url)) = mirror {
+                repo.url = url.clone();
+            }
+            repo.credentials = Credentials::find(&repo.name, home.dir())?;
+        }
+
+        Ok(Repos { repos })
+    }
+}
+
+impl Repos {
+    /// The local Maven repository, e.g. `mavenLocal = "~/.m2/repository"`
+    pub fn local(&self) -> Option<&Repo> {
+        self.repos.iter().find(|repo| repo.local_path().is_some())
+    }
+
+    /// The repos to download `pkg` from, in order.
+    /// A group included by a repo is only downloaded from the repos that include it,
+    /// so a public repo can never serve a private group (dependency confusion).
+    pub fn for_package(&self, pkg: &Package) -> Vec<&Repo> {
+        let group = pkg.namespace.as_deref().unwrap_or_default();
 
-        let repos_from_manifest = value
-            .as_table()
-            .into_iter()
-            .flat_map(|(key, value)| match Section::from_str(key) {
-                Ok(Section::Repos) => match value.as_table() {
-                    None => vec![],
-                    Some(table) => parse(table),
-                },
-                _ => vec![],
-            })
+        let exclusive = self
+            .repos
+            .iter()
+            .filter(|repo| repo.includes(group) && !repo.excludes(group))
             .collect::<Vec<_>>();
 
-        repos.extend(repos_from_manifest);
-        Repos { repos }
+        if !exclusive.is_empty() {
+            return exclusive;
+        }
+
+        self.repos
+            .iter()
+            .filter(|repo| repo.include.is_empty() && !repo.excludes(group))
+            .collect()
     }
 }
 
-#[derive(Clone, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug)]
 pub struct Repo {
     pub name: String,
     pub url: String,
+    pub credentials: Option<Credentials>,
+    /// Groups only downloaded from this repo, e.g. `com.ourcompany.*`
+    pub include: Vec<String>,
+    /// Groups never downloaded from this repo
+    pub exclude: Vec<String>,
+    /// Copy downloaded artifacts into this local repo
+    pub write: bool,
 }
 
 impl Default for Repo {
@@ -41,22 +92,137 @@ impl Default for Repo {
         Repo {
             name: "mavenCentral".into(),
             url: "https://repo1.maven.org/maven2".into(),
+            credentials: None,
+            include: vec![],
+            exclude: vec![],
+            write: false,
         }
     }
 }
 
+impl Repo {
+    /// `file://` urls and paths are local repos in the Maven layout, read in place.
+    pub fn local_path(&self) -> Option<PathBuf> {
+        let path = self.url.strip_prefix("file://").unwrap_or(&self.url);
+        match path.strip_prefix("~/") {
+            Some(relative) => home::home_dir().map(|home| home.join(relative)),
+            None if path.starts_with('/') => Some(PathBuf::from(path)),
+            None => None,
+        }
+    }
+
+    fn includes(&self, group: &str) -> bool {
+        self.include.iter().any(|pattern| matches(pattern, group))
+    }
+
+    fn excludes(&self, group: &str) -> bool {
+        self.exclude.iter().any(|pattern| matches(pattern, group))
+    }
+}
+
+/// `com.ourcompany.*` matches `com.ourcompany` and every group below it, `*` matches all.
+fn matches(pattern: &str, group: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some("") => true,
+        Some(prefix) => group.starts_with(prefix) || group == prefix.trim_end_matches('.'),
+        None => pattern == group,
+    }
+}
+
 impl Display for Repo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "{:<26}{}", "repo", self.url)
     }
 }
 
-fn parse(table: &toml_edit::Table) -> Vec<Repo> {
+/// `name = "url"` or `name = { url = "url", include = [...], exclude = [...], write = true }`
+fn parse(table: &toml_edit::Table) -> Result<Vec<Repo>> {
     table
         .iter()
-        .map(|(name, url)| Repo {
-            name: name.into(),
-            url: url.to_string().replace("\"", "").replace(" ", ""),
+        .map(|(name, item)| match item.as_table_like() {
+            None => Ok(Repo {
+                name: name.into(),
+                url: url(item, name)?,
+                ..Repo::default()
+            }),
+            Some(repo) => {
+                if ["username", "password", "token"].iter().any(|key| repo.contains_key(key)) {
+                    bail!("credentials of repo {name} do not belong in buildk.toml, use BUILDK_REPO_* or ~/.buildk/credentials.toml");
+                }
+                let url = repo.get("url").with_context(|| format!("repo {name} has no url"))?;
+                Ok(Repo {
+                    name: name.into(),
+                    url: self::url(url, name)?,
+                    include: patterns(repo.get("include")),
+                    exclude: patterns(repo.get("exclude")),
+                    write: repo.get("write").and_then(|it| it.as_bool()).unwrap_or_default(),
+                    ..Repo::default()
+                })
+            }
         })
         .collect()
 }
+
+/// `repo = "url"`, or `"*" = "url"` to mirror all repos
+fn parse_mirrors(table: &toml_edit::Table) -> Result<Vec<(String, String)>> {
+    table
+        .iter()
+        .map(|(of, item)| Ok((of.to_owned(), url(item, of)?)))
+        .collect()
+}
+
+fn url(item: &Item, name: &str) -> Result<String> {
+    item.as_str()
+        .map(|url| url.trim().trim_end_matches('/').to_owned())
+        .with_context(|| format!("expected the url of repo {name} as a string"))
+}
+
+fn patterns(item: Option<&Item>) -> Vec<String> {
+    item.and_then(|it| it.as_array())
+        .map(|array| array.iter().filter_map(|it| it.as_str()).map(str::to_owned).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use dependency::{Package, PackageKind};
+    use toml_edit::DocumentMut;
+
+    use super::Repos;
+
+    #[test]
+    fn private_groups_only_from_the_repos_including_them() {
+        let toml = r#"
+            [repos]
+            nexus = { url = "https://nexus.ourcompany.com/maven/", include = ["com.ourcompany.*"] }
+
+            [mirrors]
+            mavenCentral = "https://mirror.ourcompany.com/central"
+        "#
+        .parse::<DocumentMut>()
+        .unwrap();
+        let repos = Repos::try_from(&toml).unwrap();
+        let names = |namespace: &str| {
+            let pkg = Package::new("lib".into(), Some(namespace.into()), "1.0".into(), PackageKind::Compile);
+            repos.for_package(&pkg).iter().map(|repo| repo.name.clone()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(repos.repos[0].url, "https://mirror.ourcompany.com/central");
+        assert_eq!(repos.repos[1].url, "https://nexus.ourcompany.com/maven");
+        assert_eq!(names("com.ourcompany.billing"), ["nexus"]);
+        assert_eq!(names("com.ourcompany"), ["nexus"]);
+        assert_eq!(names("org.slf4j"), ["mavenCentral"]);
+    }
+
+    #[test]
+    fn credentials_are_refused_in_the_manifest() {
+        let toml = r#"
+            [repos]
+            nexus = { url = "https://nexus.ourcompany.com", password = "secret" }
+        "#
+        .parse::<DocumentMut>()
+        .unwrap();
+
+        assert!(Repos::try_from(&toml).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use gryf::algo::TopoSort;
+use gryf::Graph;
+use toml_edit::DocumentMut;
+
+use crate::{Manifest, Section};
+
+#[derive(Clone)]
+pub struct Workspace {
+    pub root: PathBuf,
+    pub members: Vec<Member>,
+}
+
+#[derive(Clone)]
+pub struct Member {
+    pub name: String,
+    pub manifest: Manifest,
+}
+
+impl Member {
+    pub fn depends_on(&self, other: &Member) -> bool {
+        let other = canonical(&other.manifest.project.path);
+        self.manifest.modules.iter().any(|module| canonical(module) == other)
+    }
+}
+
+impl Workspace {
+    /// Read the members listed in `[workspace]`, each member is a directory with its own buildk.toml.
+    pub fn try_new(root: &Path, manifest: &DocumentMut) -> Result<Option<Workspace>> {
+        let names = match member_names(manifest) {
+            Some(names) => names,
+            None => return Ok(None),
+        };
+
+        let members = names
+            .into_iter()
+            .map(|name| {
+                let manifest = Manifest::try_from_dir(&root.join(&name))
+                    .with_context(|| format!("Failed to read workspace member '{name}'"))?;
+                Ok(Member { name, manifest })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Some(Workspace { root: root.to_path_buf(), members }))
+    }
+
+    pub fn member(&self, name: &str) -> Option<&Member> {
+        self.members.iter().find(|member| member.name == name)
+    }
+
+    /// Members in build order, every member comes after the modules it depends on.
+    /// With a `target`, only that member and the members it depends on are returned.
+    pub fn sorted_members(&self, target: Option<&str>) -> Result<Vec<&Member>> {
+        let mut graph = Graph::new_directed();
+
+        self.members.iter().for_each(|member| {
+            graph.add_vertex(member);
+        });
+
+        graph.connect_vertices(|u, v| v.depends_on(u).then_some(()));
+
+        let sorted = TopoSort::on(&graph)
+            .run()
+            .map(|res| res.map(|v| graph[v]))
+            .collect::<Result<Vec<_>, _>>()
+            .context("cyclic dependency between workspace members")?;
+
+        let target = match target {
+            Some(name) => self.member(name).with_context(|| format!("no workspace member named '{name}'"))?,
+            None => return Ok(sorted),
+        };
+
+        let required = self.required_by(target);
+        Ok(sorted
+            .into_iter()
+            .filter(|member| required.contains(&member.name))
+            .collect())
+    }
+
+    fn required_by(&self, target: &Member) -> BTreeSet<String> {
+        let mut required = BTreeSet::from([target.name.clone()]);
+        let mut pending = vec![target];
+
+        while let Some(member) = pending.pop() {
+            for dep in self.members.iter().filter(|it| member.depends_on(it)) {
+                if required.insert(dep.name.clone()) {
+                    pending.push(dep);
+                }
+            }
+        }
+
+        required
+    }
+}
+
+impl Display for Workspace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for member in self.members.iter() {
+            writeln!(f, "{:<26}{}", "workspace.member", member.name)?;
+        }
+        Ok(())
+    }
+}
+
+fn member_names(manifest: &DocumentMut) -> Option<Vec<String>> {
+    manifest
+        .as_table()
+        .into_iter()
+        .find_map(|(key, value)| match Section::from_str(key) {
+            Ok(Section::Workspace) => Some(
+                value
+                    .get("members")
+                    .and_then(|members| members.as_array())
+                    .map(|members| members.iter().filter_map(|it| it.as_str()).map(String::from).collect())
+                    .unwrap_or_default(),
+            ),
+            _ => None,
+        })
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or(path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::Workspace;
+
+    #[test]
+    fn members_are_sorted_by_module_dependencies() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        for member in ["app", "api", "core"] {
+            fs::create_dir_all(root.join(member)).unwrap();
+        }
+        fs::write(root.join("app/buildk.toml"), "[compile]\napi = { path = \"../api\" }\n").unwrap();
+        fs::write(root.join("api/buildk.toml"), "[compile]\ncore = { path = \"../core\" }\n").unwrap();
+        fs::write(root.join("core/buildk.toml"), "").unwrap();
+
+        let manifest = "[workspace]\nmembers = [\"app\", \"api\", \"core\"]".parse().unwrap();
+        let workspace = Workspace::try_new(root, &manifest).unwrap().unwrap();
+
+        let names = |members: Vec<&super::Member>| members.iter().map(|it| it.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(workspace.sorted_members(None).unwrap()), ["core", "api", "app"]);
+        assert_eq!(names(workspace.sorted_members(Some("api")).unwrap()), ["core", "api"]);
+    }
+}
@@ -0,0 +1,131 @@
+import java.io.BufferedReader;
+import java.io.ByteArrayOutputStream;
+import java.io.IOException;
+import java.io.InputStreamReader;
+import java.io.OutputStream;
+import java.io.PrintStream;
+import java.lang.reflect.InvocationTargetException;
+import java.lang.reflect.Method;
+import java.net.InetAddress;
+import java.net.ServerSocket;
+import java.net.Socket;
+import java.net.SocketTimeoutException;
+import java.nio.file.Files;
+import java.nio.file.Path;
+import java.nio.file.Paths;
+import java.nio.file.StandardCopyOption;
+import java.nio.file.attribute.PosixFilePermissions;
+import java.security.MessageDigest;
+import java.security.SecureRandom;
+import java.util.HexFormat;
+
+import static java.nio.charset.StandardCharsets.UTF_8;
+
+/**
+ * Keeps the Kotlin compiler loaded and warm between builds, see daemon.rs.
+ * Launched from source by buildk: java -cp kotlin-compiler.jar CompileServer.java <dir> <idle timeout in seconds>
+ *
+ * One request per connection, line by line, starting with the token of the port file:
+ *   compile, the number of arguments, one argument per line -> the exit code, then the compiler output
+ *   status                                                 -> one "<key> <value>" per line
+ *   stop                                                   -> stopping
+ * Anyone can connect to the port, only the owner can read the token, a request without it is refused.
+ */
+public class CompileServer {
+    public static void main(String[] args) throws Exception {
+        Path dir = Paths.get(args[0]);
+        int idleTimeout = Integer.parseInt(args[1]);
+
+        Class<?> compiler = Class.forName("org.jetbrains.kotlin.cli.jvm.K2JVMCompiler");
+        Method exec = compiler.getMethod("exec", PrintStream.class, String[].class);
+
+        long started = System.currentTimeMillis();
+        long lastRequest = started;
+        int compilations = 0;
+
+        byte[] secret = new byte[32];
+        new SecureRandom().nextBytes(secret);
+        byte[] token = HexFormat.of().formatHex(secret).getBytes(UTF_8);
+
+        try (ServerSocket server = new ServerSocket(0, 50, InetAddress.getLoopbackAddress())) {
+            server.setSoTimeout(idleTimeout * 1000);
+            String address = server.getLocalPort() + " " + ProcessHandle.current().pid() + " " + new String(token, UTF_8);
+            Path port = dir.resolve("port");
+            Path written = dir.resolve("port.tmp");
+            Files.deleteIfExists(written);
+            Files.createFile(written, PosixFilePermissions.asFileAttribute(PosixFilePermissions.fromString("rw-------")));
+            Files.writeString(written, address);
+            Files.move(written, port, StandardCopyOption.REPLACE_EXISTING, StandardCopyOption.ATOMIC_MOVE);
+
+            try {
+                while (true) {
+                    Socket socket;
+                    try {
+                        socket = server.accept();
+                    } catch (SocketTimeoutException idle) {
+                        break;
+                    }
+
+                    try (socket) {
+                        BufferedReader in = new BufferedReader(new InputStreamReader(socket.getInputStream(), UTF_8));
+                        OutputStream out = socket.getOutputStream();
+                        String given = in.readLine();
+                        if (given == null || !MessageDigest.isEqual(token, given.getBytes(UTF_8))) {
+                            out.write("refused\n".getBytes(UTF_8));
+                            continue;
+                        }
+                        String command = in.readLine();
+
+                        if ("compile".equals(command)) {
+                            String[] compilerArgs = new String[Integer.parseInt(in.readLine())];
+                            for (int i = 0; i < compilerArgs.length; i++) {
+                                compilerArgs[i] = in.readLine();
+                            }
+
+                            ByteArrayOutputStream messages = new ByteArrayOutputStream();
+                            int code = compile(compiler, exec, compilerArgs, new PrintStream(messages, true, UTF_8));
+                            compilations++;
+
+                            out.write((code + "\n").getBytes(UTF_8));
+                            out.write(messages.toByteArray());
+                        } else if ("status".equals(command)) {
+                            long now = System.currentTimeMillis();
+                            String status = "pid " + ProcessHandle.current().pid() + "\n"
+                                    + "kotlin " + System.getProperty("kotlin.home") + "\n"
+                                    + "uptime " + (now - started) / 1000 + "\n"
+                                    + "idle " + (now - lastRequest) / 1000 + "\n"
+                                    + "idle_timeout " + idleTimeout + "\n"
+                                    + "compilations " + compilations + "\n";
+                            out.write(status.getBytes(UTF_8));
+                        } else if ("stop".equals(command)) {
+                            out.write("stopping\n".getBytes(UTF_8));
+                            break;
+                        }
+                    } catch (IOException | RuntimeException e) {
+                        e.printStackTrace();
+                    }
+                    lastRequest = System.currentTimeMillis();
+                }
+            } finally {
+                // a newer server may have replaced the port file already
+                if (Files.exists(port) && Files.readString(port).equals(address)) {
+                    Files.delete(port);
+                }
+            }
+        }
+    }
+
+    /** K2JVMCompiler().exec(messages, args).getCode(), like kotlinc. */
+    private static int compile(Class<?> compiler, Method exec, String[] args, PrintStream messages) {
+        try {
+            Object exitCode = exec.invoke(compiler.getDeclaredConstructor().newInstance(), messages, args);
+            return (int) exitCode.getClass().getMethod("getCode").invoke(exitCode);
+        } catch (InvocationTargetException e) {
+            e.getCause().printStackTrace(messages);
+        } catch (ReflectiveOperationException e) {
+            e.printStackTrace(messages);
+        }
+        // INTERNAL_ERROR
+        return 2;
+    }
+}
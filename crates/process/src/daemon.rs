@@ -0,0 +1,304 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpStream},
+    os::unix::{
+        fs::PermissionsExt,
+        process::{CommandExt, ExitStatusExt},
+    },
+    path::{Path, PathBuf},
+    process::{ExitStatus, Output, Stdio},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use manifest::home::Home;
+use util::hasher::StableHasher;
+
+use crate::ProcessBuilder;
+
+/// Stops itself when no build used it for this long.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(2 * 60 * 60);
+/// The JVM starts and compiles the server from source first.
+const START_TIMEOUT: Duration = Duration::from_secs(30);
+const SERVER: &str = include_str!("CompileServer.java");
+
+/// A JVM keeping the Kotlin compiler loaded between builds, one per Kotlin home and java binary, in `~/.buildk/daemon/<hash>`.
+/// It's started on the first compilation and found again through the `port` file it writes.
+/// Any local user can connect to the port, so every request starts with the token of the port file,
+/// which only the owner can read.
+pub struct CompileServer {
+    dir: PathBuf,
+}
+
+impl CompileServer {
+    pub fn new(home: &Home, kotlin_home: &Path, java: &Path) -> CompileServer {
+        let mut hasher = StableHasher::default();
+        kotlin_home.hash(&mut hasher);
+        // projects with another JDK get their own server, kotlinc compiles against the JDK it runs on
+        fs::canonicalize(java).unwrap_or_else(|_| java.to_path_buf()).hash(&mut hasher);
+        CompileServer {
+            dir: Self::root(home).join(format!("{:016x}", hasher.finish())),
+        }
+    }
+
+    /// Every server started before, running or not.
+    pub fn all(home: &Home) -> Vec<CompileServer> {
+        let Ok(entries) = fs::read_dir(Self::root(home)) else {
+            return vec![];
+        };
+
+        let mut dirs = entries
+            .filter_map(|entry| entry.ok().map(|it| it.path()))
+            .filter(|path| path.is_dir())
+            .collect::<Vec<_>>();
+        dirs.sort();
+        dirs.into_iter().map(|dir| CompileServer { dir }).collect()
+    }
+
+    fn root(home: &Home) -> PathBuf {
+        home.dir().join("daemon")
+    }
+
+    /// Compile with the running server, starting it with `launch` when there is none.
+    /// Arguments are passed as they are, relative paths would resolve against the directory the server started in.
+    pub fn compile(&self, launch: &ProcessBuilder, process: &ProcessBuilder) -> Result<Output> {
+        let args = process.get_args().map(|arg| arg.to_string_lossy().to_string()).collect::<Vec<_>>();
+        anyhow::ensure!(args.iter().all(|arg| !arg.contains('\n')), "an argument contains a line break");
+
+        let connection = match self.connect() {
+            Some(connection) => connection,
+            None => self.start(launch)?,
+        };
+
+        let mut request = format!("compile\n{}\n", args.len());
+        args.iter().for_each(|arg| request.push_str(&format!("{arg}\n")));
+        let response = Self::request(connection, &request)?;
+
+        let (code, messages) = response.split_once('\n').context("the compile server closed the connection")?;
+        let code = code.parse::<i32>().context("the compile server answered without an exit code")?;
+
+        Ok(Output {
+            status: ExitStatus::from_raw(code << 8),
+            stdout: vec![],
+            stderr: messages.as_bytes().to_vec(),
+        })
+    }
+
+    /// `pid`, `kotlin`, `uptime`, `idle`, `idle_timeout` and `compilations`, none when it isn't running.
+    pub fn status(&self) -> Result<Option<BTreeMap<String, String>>> {
+        let Some(connection) = self.connect() else {
+            return Ok(None);
+        };
+
+        let status = Self::request(connection, "status\n")?
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .collect();
+        Ok(Some(status))
+    }
+
+    /// False when it wasn't running.
+    pub fn stop(&self) -> Result<bool> {
+        let Some(connection) = self.connect() else {
+            return Ok(false);
+        };
+
+        Self::request(connection, "stop\n")?;
+        Ok(true)
+    }
+
+    fn request((mut stream, token): (TcpStream, String), request: &str) -> Result<String> {
+        stream.write_all(format!("{token}\n{request}").as_bytes())?;
+        stream.flush()?;
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        anyhow::ensure!(response != "refused\n", "the compile server refused the request");
+        Ok(response)
+    }
+
+    /// The server listening on the port in the `port` file (`<port> <pid> <token>`) and its token,
+    /// the file is removed when nothing listens anymore or it was written by a server without a token.
+    fn connect(&self) -> Option<(TcpStream, String)> {
+        let port_file = self.dir.join("port");
+        let content = fs::read_to_string(&port_file).ok()?;
+        let (port, token) = match content.split_whitespace().collect::<Vec<_>>()[..] {
+            [port, _pid, token] => (port.parse::<u16>().ok()?, token.to_owned()),
+            _ => {
+                let _ = fs::remove_file(port_file);
+                return None;
+            }
+        };
+
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        match TcpStream::connect_timeout(&address, Duration::from_secs(1)) {
+            Ok(stream) => Some((stream, token)),
+            Err(_) => {
+                let _ = fs::remove_file(port_file);
+                None
+            }
+        }
+    }
+
+    /// Launch `java ... CompileServer.java <dir> <idle timeout>` in its own process group, so it outlives buildk,
+    /// and wait for it to listen.
+    fn start(&self, launch: &ProcessBuilder) -> Result<(TcpStream, String)> {
+        fs::create_dir_all(&self.dir)?;
+        // the port file with the token is written here, only the owner may read it
+        fs::set_permissions(&self.dir, fs::Permissions::from_mode(0o700))?;
+        let server = self.dir.join("CompileServer.java");
+        fs::write(&server, SERVER)?;
+        let log = self.dir.join("daemon.log");
+        let out = File::create(&log)?;
+
+        let mut launch = launch.clone();
+        launch.arg(&server).arg(&self.dir).arg(IDLE_TIMEOUT.as_secs().to_string());
+        let mut child = launch
+            .build_command()
+            .stdin(Stdio::null())
+            .stdout(out.try_clone()?)
+            .stderr(out)
+            .process_group(0)
+            .spawn()
+            .with_context(|| format!("could not start the compile server {launch}"))?;
+
+        let started = Instant::now();
+        while started.elapsed() < START_TIMEOUT {
+            if let Some(connection) = self.connect() {
+                return Ok(connection);
+            }
+            if let Some(status) = child.try_wait()? {
+                let log = fs::read_to_string(&log).unwrap_or_default();
+                anyhow::bail!("the compile server exited with {status}\n{}", log.trim_end());
+            }
+            sleep(Duration::from_millis(50));
+        }
+
+        let _ = child.kill();
+        anyhow::bail!("the compile server did not start within {}s, see {}", START_TIMEOUT.as_secs(), log.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::{Read, Write},
+        net::TcpStream,
+        os::unix::fs::PermissionsExt,
+        path::Path,
+        process::Command,
+        thread::sleep,
+        time::{Duration, Instant},
+    };
+
+    use crate::ProcessBuilder;
+
+    use super::CompileServer;
+
+    /// Answers with its arguments, fails when the first one is `broken`.
+    const COMPILER: &str = r#"
+package org.jetbrains.kotlin.cli.jvm;
+
+public class K2JVMCompiler {
+    public enum ExitCode {
+        OK(0), COMPILATION_ERROR(1);
+
+        private final int code;
+
+        ExitCode(int code) { this.code = code; }
+
+        public int getCode() { return code; }
+    }
+
+    public ExitCode exec(java.io.PrintStream messages, String[] args) {
+        messages.println("compiled " + String.join(" ", args));
+        return args.length > 0 && args[0].equals("broken") ? ExitCode.COMPILATION_ERROR : ExitCode.OK;
+    }
+}
+"#;
+
+    /// The server of `CompileServer.java` with the compiler above, none without a JDK on the PATH.
+    fn server(dir: &Path) -> Option<(CompileServer, ProcessBuilder)> {
+        let stub = dir.join("stub");
+        let source = stub.join("K2JVMCompiler.java");
+        fs::create_dir_all(&stub).ok()?;
+        fs::write(&source, COMPILER).ok()?;
+        let compiled = Command::new("javac").arg("-d").arg(&stub).arg(&source).status();
+        if !compiled.is_ok_and(|status| status.success()) {
+            eprintln!("javac not found, skipping");
+            return None;
+        }
+
+        let mut launch = ProcessBuilder::new("java");
+        launch.classpath(&stub);
+        Some((CompileServer { dir: dir.join("server") }, launch))
+    }
+
+    fn send(port: &str, request: &str) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port.parse::<u16>().unwrap())).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn refuses_requests_without_the_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let Some((server, launch)) = server(dir.path()) else {
+            return;
+        };
+        server.start(&launch).unwrap();
+
+        let port_file = server.dir.join("port");
+        assert_eq!(fs::metadata(&server.dir).unwrap().permissions().mode() & 0o777, 0o700);
+        assert_eq!(fs::metadata(&port_file).unwrap().permissions().mode() & 0o777, 0o600);
+
+        let content = fs::read_to_string(&port_file).unwrap();
+        let [port, _, token] = content.split_whitespace().collect::<Vec<_>>()[..] else {
+            panic!("unexpected port file {content}");
+        };
+        assert_eq!(send(port, "compile\n1\n-Xplugin=/tmp/evil.jar\n"), "refused\n");
+        assert_eq!(send(port, "not-the-token\nstatus\n"), "refused\n");
+        assert!(send(port, &format!("{token}\nstatus\n")).contains("compilations 0"));
+
+        assert!(server.stop().unwrap());
+    }
+
+    #[test]
+    fn compiles_reports_its_status_and_stops() {
+        let dir = tempfile::tempdir().unwrap();
+        let Some((server, launch)) = server(dir.path()) else {
+            return;
+        };
+        assert!(server.status().unwrap().is_none());
+
+        let mut process = ProcessBuilder::new("kotlinc");
+        process.args(&["-d", "out", "Main.kt"]);
+        let compiled = server.compile(&launch, &process).unwrap();
+        assert!(compiled.status.success());
+        assert_eq!(String::from_utf8_lossy(&compiled.stderr), "compiled -d out Main.kt\n");
+
+        let mut process = ProcessBuilder::new("kotlinc");
+        process.args(&["broken"]);
+        assert_eq!(server.compile(&launch, &process).unwrap().status.code(), Some(1));
+
+        let status = server.status().unwrap().unwrap();
+        assert_eq!(status["compilations"], "2");
+        assert_eq!(status["idle_timeout"], super::IDLE_TIMEOUT.as_secs().to_string());
+
+        assert!(server.stop().unwrap());
+        let stopped = Instant::now();
+        while server.dir.join("port").exists() && stopped.elapsed() < Duration::from_secs(5) {
+            sleep(Duration::from_millis(50));
+        }
+        assert!(server.status().unwrap().is_none());
+        assert!(!server.stop().unwrap());
+    }
+}
@@ -1,13 +1,13 @@
-use std::{ffi::OsStr, fmt::Display, hash::{Hash, Hasher}, path::PathBuf};
+use std::{ffi::OsStr, fmt::Display, hash::{Hash, Hasher}, path::{Path, PathBuf}};
 
 use anyhow::{Context, Result};
 
 use cache::cache::{Cache, Cacheable, CacheResult};
 use manifest::{config::BuildK, Manifest};
-use util::{buildk_output::BuildkOutput, hasher::StableHasher, PartialConclusion};
+use util::{buildk_output::BuildkOutput, hasher::StableHasher, paths::resolve_executable, PartialConclusion};
 use util::buildk_output::WithBKOutput;
 
-use crate::{Process, ProcessBuilder, ProcessError, try_from};
+use crate::{compiled, Process, ProcessBuilder, ProcessError, try_from};
 
 pub struct Java<'a> {
     buildk: &'a BuildK,
@@ -21,7 +21,7 @@ impl<'a> Process<'a> for Java<'a> {
 
     fn new(buildk: &'a BuildK) -> Result<Self::Item> {
         let manifest = buildk.clone().manifest.context("manifest missing")?;
-        let java_home = Self::java_home_from_manifest(&manifest);
+        let java_home = Self::java_home_from_manifest(&manifest)?;
         Ok(
             Java {
                 buildk,
@@ -34,14 +34,23 @@ impl<'a> Process<'a> for Java<'a> {
 }
 
 impl<'a> Java<'a> {
-    fn java_home_from_manifest(manifest: &Manifest) -> PathBuf {
-        match manifest.java_home.as_ref() {
-            Some(java_home) => java_home.clone(),
-            None => match option_env!("JAVA_HOME") {
-                Some(dir) => PathBuf::from(dir),
-                None => PathBuf::from("/usr/local/Cellar/openjdk/17.0.1/"),
-            }
+    /// The `[java]` home, else `JAVA_HOME`, else the JDK of the `java` on the PATH.
+    fn java_home_from_manifest(manifest: &Manifest) -> Result<PathBuf> {
+        if let Some(java_home) = manifest
+            .java_home
+            .clone()
+            .or_else(|| std::env::var_os("JAVA_HOME").map(PathBuf::from))
+        {
+            return Ok(java_home);
         }
+
+        // e.g. /usr/bin/java links to <home>/bin/java
+        let java = resolve_executable(Path::new("java")).context("set home in [java] or JAVA_HOME")?;
+        let java = std::fs::canonicalize(&java).unwrap_or(java);
+        java.parent()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .with_context(|| format!("no Java home above {}", java.display()))
     }
 }
 impl<'a> Java<'a> {
@@ -62,6 +71,7 @@ pub struct JavaBuilder<'a> {
     java: &'a Java<'a>,
     cache: Cache,
     cache_key: u64,
+    use_cache: bool,
     process: ProcessBuilder,
 }
 
@@ -74,6 +84,7 @@ impl<'a> JavaBuilder<'a> {
             java,
             cache: Cache::load(&manifest.project.out_paths().cache),
             cache_key: 0,
+            use_cache: true,
             process: ProcessBuilder::new(""),
         }
     }
@@ -88,6 +99,29 @@ impl<'a> JavaBuilder<'a> {
         self
     }
 
+    pub fn sources(&mut self, sources: Vec<&PathBuf>) -> &mut Self {
+        for src in sources.iter() {
+            self.process.sources(src);
+        }
+        self
+    }
+
+    pub fn target(&mut self, target: &PathBuf) -> &mut Self {
+        self.process.destination(target);
+        self
+    }
+
+    pub fn cache_key(&mut self, key: u64) -> &mut Self {
+        self.cache_key = key;
+        self
+    }
+
+    /// Always execute the process, e.g. when the caller already knows the sources changed.
+    pub fn no_cache(&mut self) -> &mut Self {
+        self.use_cache = false;
+        self
+    }
+
     pub fn workdir(&mut self, workdir: &PathBuf) -> &mut Self {
         self.process.cwd(workdir);
         self
@@ -123,14 +157,8 @@ impl<'a> JavaBuilder<'a> {
 
     pub fn compile(&mut self, output: &mut BuildkOutput) -> BuildkOutput {
         self.process.program(self.java.compiler());
-        let mut cache = self.cache.clone();
-        match self.cache(&mut cache, self.process.clone()) {
-            Ok(result) => result.add_to_output(output).to_owned(),
-            Err(err) => {
-                //println!("\r{:#}", err.to_string().as_red());
-                output.conclude(PartialConclusion::FAILED).stderr(err.to_string()).to_owned()
-            }
-        }
+        let result = self.cache(&mut self.cache.clone(), self.process.clone());
+        compiled(output, result)
     }
 }
 
@@ -144,7 +172,7 @@ impl<'a> Cacheable for JavaBuilder<'a> {
 
     fn cache(&mut self, cache: &mut Cache, item: Self::Item) -> Result<CacheResult> {
         let key = self.fingerprint(item.clone());
-        let partial_conclusion = match cache.contains_key(&key) {
+        let partial_conclusion = match self.use_cache && cache.contains_key(&key) {
             true => PartialConclusion::CACHED,
             false => {
                 let output = item.output()?;
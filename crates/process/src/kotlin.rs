@@ -1,7 +1,8 @@
 use std::{
     fmt::Display,
     hash::{Hash, Hasher},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process::Output,
 };
 
 use anyhow::{Context, Result};
@@ -9,10 +10,11 @@ use anyhow::{Context, Result};
 use cache::cache::{Cache, CacheResult, Cacheable};
 use manifest::{config::BuildK, Manifest};
 use util::{
-    buildk_output::BuildkOutput, colorize::Colorize, hasher::StableHasher, PartialConclusion,
+    buildk_output::BuildkOutput, colorize::Colorize, hasher::StableHasher, paths::resolve_executable,
+    PartialConclusion,
 };
 
-use crate::{try_from, Process, ProcessBuilder, ProcessError};
+use crate::{compiled, daemon::CompileServer, try_from, Process, ProcessBuilder, ProcessError};
 
 // // https://mvnrepository.com/artifact/org.jetbrains.kotlin/kotlin-compiler-embeddable
 // runtimeOnly("org.jetbrains.kotlin:kotlin-compiler-embeddable:1.9.22")
@@ -72,12 +74,42 @@ impl Kotlin<'_> {
     pub fn runner(&self) -> PathBuf {
         self.bin.join("kotlin")
     }
+
+    /// The compile server of this Kotlin home and the java command launching it,
+    /// none when kotlin-compiler.jar isn't found, e.g. kotlinc is a wrapper script somewhere else.
+    pub fn daemon(&self) -> Option<(CompileServer, ProcessBuilder)> {
+        // Homebrew keeps the distribution in libexec
+        let home = [self.home.join("libexec"), self.home.clone()]
+            .into_iter()
+            .find(|home| home.join("lib").join("kotlin-compiler.jar").is_file())?;
+
+        let java = self
+            .buildk
+            .manifest
+            .as_ref()
+            .and_then(|it| it.java_home.clone())
+            .or_else(|| std::env::var_os("JAVA_HOME").map(PathBuf::from))
+            .map(|home| home.join("bin").join("java"))
+            .filter(|java| java.is_file())
+            .or_else(|| resolve_executable(Path::new("java")).ok())?;
+
+        let mut launch = ProcessBuilder::new(&java);
+        launch
+            .arg(format!("-Dkotlin.home={}", home.display()))
+            .args(&["-Xss2m", "-Djava.awt.headless=true"])
+            .classpath(home.join("lib").join("kotlin-compiler.jar"));
+
+        Some((CompileServer::new(&self.buildk.home, &self.home, &java), launch))
+    }
 }
 
 pub struct KotlinBuilder<'a> {
     kotlin: &'a Kotlin<'a>,
     cache: Cache,
     cache_key: u64,
+    use_cache: bool,
+    use_daemon: bool,
+    options: Vec<String>,
     process: ProcessBuilder,
 }
 
@@ -90,6 +122,9 @@ impl<'a> KotlinBuilder<'a> {
             kotlin,
             cache: Cache::load(&manifest.project.out_paths().cache),
             cache_key: 0,
+            use_cache: true,
+            use_daemon: false,
+            options: manifest.kotlin_options.args(),
             process: ProcessBuilder::new(""),
         }
     }
@@ -131,6 +166,12 @@ impl<'a> KotlinBuilder<'a> {
         self
     }
 
+    /// Always execute the process, e.g. when the caller already knows the sources changed.
+    pub fn no_cache(&mut self) -> &mut Self {
+        self.use_cache = false;
+        self
+    }
+
     pub fn include_runtime(&mut self) -> &mut Self {
         self.process.include_runtime();
         self
@@ -146,8 +187,11 @@ impl<'a> KotlinBuilder<'a> {
     pub fn compile(&mut self, output: &mut BuildkOutput) -> BuildkOutput {
         self.process.program(self.kotlin.compiler());
         self.process.include_runtime();
-        self.execute_with_cache(output, &self.process.clone())
-            .to_owned()
+        self.process.args(&self.options);
+        self.use_daemon = self.kotlin.buildk.daemon;
+
+        let result = self.cache(&mut self.cache.clone(), self.process.clone());
+        compiled(output, result)
     }
 
     fn execute_with_cache(
@@ -169,15 +213,33 @@ impl<'a> KotlinBuilder<'a> {
     }
 }
 
+impl KotlinBuilder<'_> {
+    /// Through the compile server when compiling, falling back to kotlinc when it's unavailable.
+    fn output(&self, item: &ProcessBuilder) -> Result<Output> {
+        let daemon = match self.use_daemon {
+            true => self.kotlin.daemon(),
+            false => None,
+        };
+
+        match daemon {
+            Some((server, launch)) => server.compile(&launch, item).or_else(|err| {
+                eprintln!("\r{}", format!("compile server unavailable, using kotlinc: {err:#}").as_yellow());
+                item.output()
+            }),
+            None => item.output(),
+        }
+    }
+}
+
 impl Cacheable for KotlinBuilder<'_> {
     type Item = ProcessBuilder;
 
     fn cache(&mut self, cache: &mut Cache, item: Self::Item) -> Result<CacheResult> {
         let key = self.fingerprint(item.clone());
-        let partial_conclusion = match cache.contains_key(&key) {
+        let partial_conclusion = match self.use_cache && cache.contains_key(&key) {
             true => PartialConclusion::CACHED,
             false => {
-                let output = item.output()?;
+                let output = self.output(&item)?;
                 let output = try_from(&item, output)?;
                 cache.insert(key, output);
                 PartialConclusion::SUCCESS
@@ -10,8 +10,13 @@ use std::{
 };
 
 use anyhow::{Context, Result};
+use cache::cache::CacheResult;
 use manifest::config::BuildK;
+use util::{
+    buildk_output::BuildkOutput, colorize::Colorize, diagnostic::Diagnostic, message, PartialConclusion,
+};
 
+pub mod daemon;
 pub mod java;
 pub mod kotlin;
 
@@ -298,6 +303,41 @@ pub fn exit_status_to_string(status: ExitStatus) -> String {
     }
 }
 
+/// Conclude `output` with the result of kotlinc or javac, printing the diagnostics it reported.
+fn compiled(output: &mut BuildkOutput, result: Result<CacheResult>) -> BuildkOutput {
+    let diagnostics = match result {
+        // the compiler exited successfully, whatever it wrote to stderr are warnings
+        Ok(cache_res) => {
+            let stderr = cache_res.stderr.unwrap_or_default();
+            output
+                .conclude(cache_res.conclusion)
+                .status(cache_res.status)
+                .stdout(cache_res.stdout.unwrap_or_default());
+            Diagnostic::parse(&stderr)
+        }
+        Err(err) => {
+            let stderr = err
+                .downcast_ref::<ProcessError>()
+                .and_then(|it| it.stderr.as_deref())
+                .map(String::from_utf8_lossy)
+                .unwrap_or_default();
+            let diagnostics = Diagnostic::parse(&stderr);
+            if diagnostics.is_empty() {
+                println!("\r{:#}", err.to_string().as_red());
+            }
+
+            output.conclude(PartialConclusion::FAILED).stderr(err.to_string());
+            diagnostics
+        }
+    };
+
+    for diagnostic in diagnostics.iter() {
+        eprintln!("\r{}\n", diagnostic.render());
+        message::emit(diagnostic.message());
+    }
+    output.diagnose(diagnostics).to_owned()
+}
+
 fn try_from(
     cmd: &ProcessBuilder,
     output: std::process::Output,
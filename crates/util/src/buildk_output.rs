@@ -1,3 +1,7 @@
+use serde_json::{json, Value};
+
+use crate::colorize;
+use crate::diagnostic::Diagnostic;
 use crate::PartialConclusion;
 use crate::timer::Timer;
 
@@ -8,10 +12,35 @@ pub struct BuildkOutput {
     stdout: Option<String>,
     stderr: Option<String>,
     status: i32,
+    failure: Option<Failure>,
+    diagnostics: Vec<Diagnostic>,
     cache_hit: bool,
     timed: Timer,
 }
 
+/// Why a command failed, every class exits buildk with its own code.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Failure {
+    /// buildk.toml is missing or invalid, or Kotlin or Java are not found
+    Config,
+    Compile,
+    Test,
+    /// A download failed, or an artifact is missing offline
+    Network,
+}
+
+impl Failure {
+    /// 1 is left for failures without a class and 2 for clap, which exits with it on invalid arguments.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Failure::Compile => 3,
+            Failure::Test => 4,
+            Failure::Network => 5,
+            Failure::Config => 6,
+        }
+    }
+}
+
 impl Default for BuildkOutput {
     fn default() -> Self {
         BuildkOutput {
@@ -20,6 +49,8 @@ impl Default for BuildkOutput {
             stdout: None,
             stderr: None,
             status: 0,
+            failure: None,
+            diagnostics: vec![],
             cache_hit: false,
             timed: Timer::start(),
         }
@@ -50,6 +81,10 @@ impl BuildkOutput {
         if other.status != 0 {
             self.status = other.status;
         }
+        if other.failure.is_some() {
+            self.failure = other.failure;
+        }
+        self.diagnostics.extend(other.diagnostics);
         self.to_owned()
     }
     
@@ -63,6 +98,18 @@ impl BuildkOutput {
         }
         self
     }
+    /// Conclude as failed, the first class given is kept.
+    pub fn fail(&mut self, failure: Failure) -> &mut Self {
+        self.conclusion = PartialConclusion::FAILED;
+        self.failure.get_or_insert(failure);
+        self
+    }
+
+    pub fn diagnose(&mut self, diagnostics: Vec<Diagnostic>) -> &mut Self {
+        self.diagnostics.extend(diagnostics);
+        self
+    }
+
     pub fn cache_hit(&mut self) -> &mut Self {
         self.cache_hit = true;
         self
@@ -112,4 +159,52 @@ impl BuildkOutput {
     pub fn get_stdout(&self) -> Option<String> {
         self.stdout.clone()
     }
+    pub fn get_failure(&self) -> Option<Failure> {
+        self.failure
+    }
+    pub fn get_diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// The `command-finished` message of `--message-format json`.
+    pub fn message(&self) -> Value {
+        json!({
+            "reason": "command-finished",
+            "command": self.command,
+            "conclusion": format!("{:?}", self.conclusion).to_lowercase(),
+            "failure": self.failure.map(|it| format!("{it:?}").to_lowercase()),
+            "exit_code": self.exit_code(),
+            "elapsed_ms": self.timed.millis(),
+            "cache_hit": self.cache_hit,
+            "stdout": self.stdout.as_deref().map(colorize::strip),
+            "stderr": self.stderr.as_deref().map(colorize::strip),
+        })
+    }
+
+    /// 0 unless the command failed, see [Failure::exit_code].
+    pub fn exit_code(&self) -> u8 {
+        match (&self.conclusion, self.failure) {
+            (PartialConclusion::FAILED, Some(failure)) => failure.exit_code(),
+            (PartialConclusion::FAILED, None) => 1,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BuildkOutput, Failure};
+    use crate::PartialConclusion;
+
+    #[test]
+    fn exit_code_of_the_first_failure() {
+        assert_eq!(BuildkOutput::new("build").conclude(PartialConclusion::SUCCESS).exit_code(), 0);
+        assert_eq!(BuildkOutput::new("build").conclude(PartialConclusion::FAILED).exit_code(), 1);
+
+        let mut fetch = BuildkOutput::new("fetch");
+        fetch.fail(Failure::Network);
+        let mut add = BuildkOutput::new("add");
+        add.conclude(PartialConclusion::SUCCESS).apply(fetch);
+        assert_eq!(add.fail(Failure::Config).exit_code(), 5);
+    }
 }
@@ -0,0 +1,160 @@
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::paths;
+
+const MAGIC: u32 = 0xCAFE_BABE;
+
+/// Read the `SourceFile` attribute of a compiled `.class` file, e.g. `Main.kt`.
+/// Returns `None` when the compiler did not emit the attribute.
+pub fn source_file(path: &Path) -> Result<Option<String>> {
+    let bytes = paths::read_bytes(path)?;
+    parse_source_file(&bytes).with_context(|| format!("invalid class file {}", path.display()))
+}
+
+fn parse_source_file(bytes: &[u8]) -> Result<Option<String>> {
+    let mut reader = Reader { bytes, pos: 0 };
+    ensure!(reader.u4()? == MAGIC, "missing magic number");
+    reader.skip(4)?; // minor and major version
+
+    let pool = reader.constant_pool()?;
+
+    reader.skip(6)?; // access flags, this class, super class
+    let interfaces = reader.u2()? as usize;
+    reader.skip(interfaces * 2)?;
+
+    for _ in 0..2 {
+        // fields, then methods
+        let members = reader.u2()?;
+        for _ in 0..members {
+            reader.skip(6)?; // access flags, name, descriptor
+            reader.skip_attributes()?;
+        }
+    }
+
+    let attributes = reader.u2()?;
+    for _ in 0..attributes {
+        let name = reader.u2()?;
+        let len = reader.u4()? as usize;
+        if pool.utf8(name) == Some("SourceFile") {
+            let index = reader.u2()?;
+            return Ok(pool.utf8(index).map(str::to_owned));
+        }
+        reader.skip(len)?;
+    }
+
+    Ok(None)
+}
+
+struct ConstantPool {
+    utf8: Vec<Option<String>>,
+}
+
+impl ConstantPool {
+    fn utf8(&self, index: u16) -> Option<&str> {
+        self.utf8.get(index as usize)?.as_deref()
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        let end = self.pos + len;
+        ensure!(end <= self.bytes.len(), "unexpected end of file");
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<()> {
+        self.take(len).map(|_| ())
+    }
+
+    fn u1(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u2(&mut self) -> Result<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u4(&mut self) -> Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn skip_attributes(&mut self) -> Result<()> {
+        let count = self.u2()?;
+        for _ in 0..count {
+            self.skip(2)?;
+            let len = self.u4()? as usize;
+            self.skip(len)?;
+        }
+        Ok(())
+    }
+
+    // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.4
+    fn constant_pool(&mut self) -> Result<ConstantPool> {
+        let count = self.u2()? as usize;
+        let mut utf8 = vec![None; count];
+        let mut index = 1;
+
+        while index < count {
+            let tag = self.u1()?;
+            match tag {
+                1 => {
+                    let len = self.u2()? as usize;
+                    let text = String::from_utf8_lossy(self.take(len)?).to_string();
+                    utf8[index] = Some(text);
+                }
+                7 | 8 | 16 | 19 | 20 => self.skip(2)?,
+                15 => self.skip(3)?,
+                3 | 4 | 9 | 10 | 11 | 12 | 17 | 18 => self.skip(4)?,
+                5 | 6 => {
+                    // long and double occupy two entries
+                    self.skip(8)?;
+                    index += 1;
+                }
+                _ => bail!("unknown constant pool tag {tag}"),
+            }
+            index += 1;
+        }
+
+        Ok(ConstantPool { utf8 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_source_file;
+
+    fn utf8(bytes: &mut Vec<u8>, text: &str) {
+        bytes.push(1);
+        bytes.extend((text.len() as u16).to_be_bytes());
+        bytes.extend(text.as_bytes());
+    }
+
+    #[test]
+    fn reads_source_file_attribute() {
+        let mut bytes = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 61];
+        bytes.extend(5u16.to_be_bytes()); // constant pool count
+        utf8(&mut bytes, "SourceFile");
+        utf8(&mut bytes, "Main.kt");
+        bytes.extend([5, 0, 0, 0, 0, 0, 0, 0, 0]); // long, occupies entry 3 and 4
+        bytes.extend([0; 6]); // access flags, this, super
+        bytes.extend([0; 6]); // interfaces, fields, methods
+        bytes.extend(1u16.to_be_bytes());
+        bytes.extend(1u16.to_be_bytes());
+        bytes.extend(2u32.to_be_bytes());
+        bytes.extend(2u16.to_be_bytes());
+
+        let source = parse_source_file(&bytes).unwrap();
+        assert_eq!(source, Some("Main.kt".to_owned()));
+    }
+}
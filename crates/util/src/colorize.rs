@@ -96,3 +96,26 @@ impl Colorize for String {
     fn as_gray(&self) -> String { format!("\x1b[37m{self}\x1b[0m") }
     fn as_white(&self) -> String { format!("\x1b[38m{self}\x1b[0m") }
 }
+
+/// `text` without the escape sequences added by [Colorize].
+pub fn strip(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => chars.by_ref().take_while(|it| *it != 'm').for_each(drop),
+            c => plain.push(c),
+        }
+    }
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{strip, Colorize};
+
+    #[test]
+    fn strips_colors() {
+        assert_eq!(strip(&format!("{} selected", "hello:1.1".as_green())), "hello:1.1 selected");
+    }
+}
@@ -0,0 +1,329 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+
+use crate::colorize::Colorize;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Info => write!(f, "info"),
+        }
+    }
+}
+
+/// Something kotlinc or javac reported, `<file>:<line>[:<column>]: <severity>: <message>` or `<severity>: <message>`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: Option<PathBuf>,
+    /// 1-based line and column
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    /// The last column underlined, inclusive
+    pub end_column: Option<usize>,
+    pub message: String,
+    /// The source line printed below the message
+    pub source: Option<String>,
+}
+
+impl Diagnostic {
+    /// Every diagnostic in the output of kotlinc or javac, with the source line and carets printed below it.
+    pub fn parse(output: &str) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = vec![];
+        let mut details: Vec<&str> = vec![];
+
+        for line in output.lines() {
+            match Diagnostic::header(line) {
+                Some(diagnostic) => {
+                    if let Some(last) = diagnostics.last_mut() {
+                        last.details(&details);
+                    }
+                    details.clear();
+                    diagnostics.push(diagnostic);
+                }
+                None => details.push(line),
+            }
+        }
+        if let Some(last) = diagnostics.last_mut() {
+            last.details(&details);
+        }
+
+        diagnostics
+    }
+
+    fn header(line: &str) -> Option<Diagnostic> {
+        [Severity::Error, Severity::Warning, Severity::Info]
+            .into_iter()
+            .find_map(|severity| {
+                if let Some(message) = line.strip_prefix(&format!("{severity}: ")) {
+                    return Some(Diagnostic::new(severity, None, message));
+                }
+                let (location, message) = line.split_once(&format!(": {severity}: "))?;
+                Some(Diagnostic::new(severity, Some(location), message))
+            })
+    }
+
+    fn new(severity: Severity, location: Option<&str>, message: &str) -> Diagnostic {
+        // the file itself may contain a colon, e.g. C:\src\Main.kt:3:5, javac leaves out the column
+        let number = |it: &str| it.parse::<usize>().ok();
+        let parts = location.map(|it| it.rsplitn(3, ':').collect::<Vec<_>>()).unwrap_or_default();
+        let (file, line, column) = match parts[..] {
+            [column, line, file] if number(line).is_some() && number(column).is_some() => (Some(file), number(line), number(column)),
+            _ => match location.and_then(|it| it.rsplit_once(':')) {
+                Some((file, line)) if number(line).is_some() => (Some(file), number(line), None),
+                _ => (location, None, None),
+            },
+        };
+
+        Diagnostic {
+            severity,
+            file: file.map(PathBuf::from),
+            line,
+            column,
+            end_column: None,
+            message: message.to_owned(),
+            source: None,
+        }
+    }
+
+    /// The lines below the message, the source line and its carets when there's a location.
+    /// javac explains more below them, the column is where its caret is.
+    fn details(&mut self, lines: &[&str]) {
+        // javac ends with a count, e.g. `1 error`, and notes, e.g. about unchecked operations
+        let summary = |line: &str| match line.split_once(' ') {
+            Some((count, noun)) => count.parse::<usize>().is_ok() && ["error", "errors", "warning", "warnings"].contains(&noun),
+            None => false,
+        };
+        let mut lines = lines
+            .iter()
+            .copied()
+            .filter(|line| !summary(line) && !line.starts_with("Note: "))
+            .collect::<Vec<_>>();
+        while lines.last().is_some_and(|it| it.trim().is_empty()) {
+            lines.pop();
+        }
+
+        let caret = lines.iter().position(|it| it.trim().chars().all(|c| c == '^'));
+        if let (Some(caret), Some(_)) = (caret.filter(|it| *it > 0), self.line) {
+            let carets = lines[caret].trim();
+            let column = *self.column.get_or_insert(lines[caret].len() - lines[caret].trim_start().len() + 1);
+            self.end_column = Some(column + carets.len() - 1);
+            self.source = Some(lines[caret - 1].to_owned());
+            lines.drain(caret - 1..=caret);
+        }
+
+        lines.iter().for_each(|line| {
+            self.message.push('\n');
+            self.message.push_str(line);
+        });
+    }
+
+    /// The message, then the source line with the reported range underlined, like rustc.
+    pub fn render(&self) -> String {
+        let severity = match self.severity {
+            Severity::Error => self.severity.to_string().as_red(),
+            Severity::Warning => self.severity.to_string().as_yellow(),
+            Severity::Info => self.severity.to_string().as_blue(),
+        };
+        let mut rendered = format!("{severity}: {}", self.message);
+
+        let Some(file) = &self.file else {
+            return rendered;
+        };
+        let (line, column) = (self.line.unwrap_or(1), self.column.unwrap_or(1));
+        let gutter = " ".repeat(line.to_string().len());
+        rendered.push_str(&format!("\n{gutter}{} {}:{line}:{column}", "-->".as_blue(), file.display()));
+
+        if let Some(source) = &self.source {
+            let width = self.end_column.map(|end| end + 1 - column).unwrap_or(1);
+            let carets = format!("{}{}", " ".repeat(column - 1), "^".repeat(width));
+            let carets = match self.severity {
+                Severity::Error => carets.as_red(),
+                _ => carets.as_yellow(),
+            };
+            let bar = "|".as_blue();
+            rendered.push_str(&format!("\n{gutter} {bar}\n{} {bar} {source}\n{gutter} {bar} {carets}", line.to_string().as_blue()));
+        }
+
+        rendered
+    }
+
+    /// The `compiler-message` message of `--message-format json`.
+    pub fn message(&self) -> Value {
+        json!({
+            "reason": "compiler-message",
+            "level": self.severity.to_string(),
+            "file": self.file,
+            "line": self.line,
+            "column": self.column,
+            "end_column": self.end_column,
+            "message": self.message,
+        })
+    }
+
+    /// e.g. `2 errors, 1 warning`, empty without errors or warnings.
+    pub fn count(diagnostics: &[Diagnostic]) -> String {
+        [(Severity::Error, "error"), (Severity::Warning, "warning")]
+            .into_iter()
+            .map(|(severity, noun)| (diagnostics.iter().filter(|it| it.severity == severity).count(), noun))
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, noun)| match count {
+                1 => format!("1 {noun}"),
+                count => format!("{count} {noun}s"),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// A SARIF 2.1.0 log of `diagnostics`, with file locations relative to `root`.
+    pub fn sarif(diagnostics: &[Diagnostic], root: &Path) -> Value {
+        let results = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let level = match diagnostic.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                    Severity::Info => "note",
+                };
+                let mut result = json!({ "level": level, "message": { "text": diagnostic.message } });
+
+                if let Some(file) = &diagnostic.file {
+                    let uri = file.strip_prefix(root).unwrap_or(file).to_string_lossy().replace('\\', "/");
+                    let mut location = json!({ "artifactLocation": { "uri": uri } });
+                    if let (Some(line), Some(column)) = (diagnostic.line, diagnostic.column) {
+                        location["region"] = json!({ "startLine": line, "startColumn": column });
+                        if let Some(end) = diagnostic.end_column {
+                            // exclusive in SARIF
+                            location["region"]["endColumn"] = json!(end + 1);
+                        }
+                    }
+                    result["locations"] = json!([{ "physicalLocation": location }]);
+                }
+                result
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": { "driver": { "name": "buildk" } },
+                "results": results,
+            }],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::colorize;
+
+    use super::{Diagnostic, Severity};
+
+    const OUTPUT: &str = "\
+/project/src/Main.kt:3:5: error: unresolved reference: foo
+    foo()
+    ^^^
+/project/src/Main.kt:7:9: warning: variable 'x' is never used
+    val x = 1
+        ^
+warning: classpath entry points to a non-existent location: /nope
+";
+
+    #[test]
+    fn parses_kotlinc_output() {
+        let diagnostics = Diagnostic::parse(OUTPUT);
+
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(
+            diagnostics[0],
+            Diagnostic {
+                severity: Severity::Error,
+                file: Some(PathBuf::from("/project/src/Main.kt")),
+                line: Some(3),
+                column: Some(5),
+                end_column: Some(7),
+                message: "unresolved reference: foo".into(),
+                source: Some("    foo()".into()),
+            }
+        );
+        assert_eq!(diagnostics[1].end_column, Some(9));
+        assert_eq!(diagnostics[2].file, None);
+        assert_eq!(diagnostics[2].message, "classpath entry points to a non-existent location: /nope");
+        assert_eq!(Diagnostic::count(&diagnostics), "1 error, 2 warnings");
+    }
+
+    #[test]
+    fn parses_javac_output() {
+        let output = "\
+/project/src/Legacy.java:4: error: cannot find symbol
+        greet(name);
+        ^
+  symbol:   method greet(String)
+  location: class Legacy
+Note: /project/src/Legacy.java uses unchecked or unsafe operations.
+1 error
+";
+        let diagnostics = Diagnostic::parse(output);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].file, Some(PathBuf::from("/project/src/Legacy.java")));
+        assert_eq!((diagnostics[0].line, diagnostics[0].column, diagnostics[0].end_column), (Some(4), Some(9), Some(9)));
+        assert_eq!(diagnostics[0].source.as_deref(), Some("        greet(name);"));
+        assert_eq!(
+            diagnostics[0].message,
+            "cannot find symbol\n  symbol:   method greet(String)\n  location: class Legacy"
+        );
+    }
+
+    #[test]
+    fn keeps_blank_lines_within_a_message() {
+        let output = "\
+error: argument type mismatch
+
+expected: String
+actual: Int
+
+warning: unused variable
+";
+        let diagnostics = Diagnostic::parse(output);
+
+        assert_eq!(diagnostics[0].message, "argument type mismatch\n\nexpected: String\nactual: Int");
+        assert_eq!(diagnostics[1].message, "unused variable");
+    }
+
+    #[test]
+    fn renders_a_snippet() {
+        let rendered = colorize::strip(&Diagnostic::parse(OUTPUT)[0].render());
+
+        assert_eq!(
+            rendered,
+            "error: unresolved reference: foo\n --> /project/src/Main.kt:3:5\n  |\n3 |     foo()\n  |     ^^^"
+        );
+    }
+
+    #[test]
+    fn sarif_locations_are_relative() {
+        let sarif = Diagnostic::sarif(&Diagnostic::parse(OUTPUT), Path::new("/project"));
+        let results = &sarif["runs"][0]["results"];
+
+        assert_eq!(results[0]["level"], "error");
+        assert_eq!(results[0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"], "src/Main.kt");
+        assert_eq!(results[0]["locations"][0]["physicalLocation"]["region"]["endColumn"], 8);
+        assert_eq!(results[2]["level"], "warning");
+        assert!(results[2]["locations"].is_null());
+    }
+}
@@ -1,6 +1,13 @@
 #![allow(deprecated)]
 
+use std::fs::File;
 use std::hash::{Hasher, SipHasher};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
 
 pub struct StableHasher(SipHasher);
 
@@ -19,3 +26,84 @@ impl Hasher for StableHasher {
         self.0.write(bytes)
     }
 }
+
+/// Hex encoded sha256 digest of the file contents.
+pub fn sha256(path: &Path) -> Result<String> {
+    digest::<Sha256>(path)
+}
+
+/// Hex encoded sha1 digest of the file contents.
+pub fn sha1(path: &Path) -> Result<String> {
+    digest::<Sha1>(path)
+}
+
+fn digest<D: Digest + io::Write>(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = D::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+/// The checksums Maven repositories publish next to an artifact, strongest first.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Checksum {
+    Sha256,
+    Sha1,
+}
+
+impl Checksum {
+    pub const ALL: [Checksum; 2] = [Checksum::Sha256, Checksum::Sha1];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Checksum::Sha256 => "sha256",
+            Checksum::Sha1 => "sha1",
+        }
+    }
+
+    pub fn digest(&self, path: &Path) -> Result<String> {
+        match self {
+            Checksum::Sha256 => sha256(path),
+            Checksum::Sha1 => sha1(path),
+        }
+    }
+
+    /// Where the verified checksum of `path` is recorded, e.g. `pkg.jar.sha256`
+    pub fn sidecar(&self, path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".");
+        name.push(self.extension());
+        PathBuf::from(name)
+    }
+
+    /// Where the sha256 of a file is recorded when the repository published no checksum, e.g. `pkg.jar.unverified`.
+    /// It can't be verified, but later corruption is still found.
+    pub fn unverified(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".unverified");
+        PathBuf::from(name)
+    }
+
+    /// Compare against a published checksum file, which may be followed by the file name.
+    pub fn matches(&self, path: &Path, published: &str) -> Result<bool> {
+        let expected = published.split_whitespace().next().unwrap_or_default().to_lowercase();
+        Ok(self.digest(path)? == expected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Checksum;
+
+    #[test]
+    fn matches_published_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pkg.jar");
+        std::fs::write(&path, "buildk").unwrap();
+
+        let sha1 = Checksum::Sha1.digest(&path).unwrap();
+        assert!(Checksum::Sha1.matches(&path, &format!("{}  pkg.jar\n", sha1.to_uppercase())).unwrap());
+        assert!(!Checksum::Sha256.matches(&path, &sha1).unwrap());
+        assert_eq!(Checksum::Sha256.sidecar(&path).extension().unwrap(), "sha256");
+    }
+}
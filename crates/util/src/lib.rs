@@ -2,8 +2,11 @@ use colorize::Colorize;
 use terminal::Printable;
 
 pub mod buildk_output;
+pub mod class_file;
 pub mod colorize;
+pub mod diagnostic;
 pub mod hasher;
+pub mod message;
 pub mod paths;
 pub mod terminal;
 pub mod timer;
@@ -0,0 +1,49 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::{Mutex, OnceLock};
+
+use serde_json::Value;
+
+/// The original stdout, set with `--message-format json`.
+static JSON: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// Emit newline-delimited JSON messages on stdout.
+/// Everything else printed to stdout, the status line, spinners and tool output, goes to stderr instead,
+/// so stdout is nothing but messages.
+///
+/// Unix only: kotlinc, javac and the tests inherit file descriptor 1, so it is pointed at stderr
+/// and the messages are written to a duplicate of the original. Elsewhere this fails and the output stays text.
+#[cfg(unix)]
+pub fn json() -> io::Result<()> {
+    use std::os::fd::FromRawFd;
+
+    io::stdout().flush()?;
+
+    // SAFETY: plain descriptor calls, the duplicate is owned by the File below and by nothing else
+    let stdout = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if stdout < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let _ = JSON.set(Mutex::new(unsafe { File::from_raw_fd(stdout) }));
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn json() -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "only supported on unix"))
+}
+
+pub fn is_json() -> bool {
+    JSON.get().is_some()
+}
+
+/// Write `message` as one line of JSON, a no-op unless `--message-format json` is given.
+pub fn emit(message: Value) {
+    if let Some(Ok(mut stdout)) = JSON.get().map(Mutex::lock) {
+        let _ = writeln!(stdout, "{message}");
+    }
+}
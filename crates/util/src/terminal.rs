@@ -22,6 +22,10 @@ impl Terminal {
     }
 
     pub fn start_spin(&mut self, row: u16, text: &str) {
+        // progress is not a message, stderr stays readable for whoever collects it
+        if crate::message::is_json() {
+            return;
+        }
         let spinner = Spinner::new(Spinners::Dots7, text.into());
         self.spinners.insert(row, spinner);
     }
@@ -31,6 +35,12 @@ impl Terminal {
             spinner.stop();
         }
     }
+
+    /// Replace the text of a running spinner, e.g. to show progress.
+    pub fn update_spin(&mut self, row: u16, text: &str) {
+        self.stop_spin(row);
+        self.start_spin(row, text);
+    }
     /*
     pub fn next_row(&self) -> u16 {
         match self.rows.iter().max() {
@@ -22,6 +22,10 @@ impl Timer {
             .unwrap_or(format!("{} s", seconds))
     }
 
+    pub fn millis(&self) -> f64 {
+        self.time.elapsed().as_secs_f64() * 1_000.
+    }
+
     fn reminder(time: f64, label: &str) -> Option<String> {
         if time >= 1.0 {
             Some(format!("{:.0} {}", time, label))
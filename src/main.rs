@@ -1,19 +1,28 @@
-use anyhow::Result;
+use std::process::ExitCode;
+
 use command::Cli;
 use manifest::config::BuildK;
+use util::diagnostic::Diagnostic;
+use util::message;
 use util::terminal::Terminal;
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let buildk = BuildK::new();
     let mut terminal = Terminal::default();
     let mut cli = Cli::init();
-    let output = cli.command.apply(&buildk);
+    let output = cli.apply(&buildk);
 
-    if !cli.is_quiet() {
+    if message::is_json() {
+        message::emit(output.message());
+    } else if !cli.is_quiet() {
+        let diagnostics = match Diagnostic::count(output.get_diagnostics()) {
+            count if count.is_empty() => count,
+            count => format!(", {count}"),
+        };
         terminal.print_row(
             0,
             &format!(
-                "\r{:<6} {:<12} ▸ {}",
+                "\r{:<6} {:<12} ▸ {}{diagnostics}",
                 output.conclusion().color_symbol(),
                 output.get_command(),
                 output.elapsed()
@@ -24,5 +33,5 @@ fn main() -> Result<()> {
         }
     }
 
-    Ok(())
+    ExitCode::from(output.exit_code())
 }